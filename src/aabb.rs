@@ -0,0 +1,169 @@
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Flat objects such as axis-aligned triangles would otherwise get a zero-width slab, which
+// the slab test can miss and which gives a misleading zero surface area.
+const MIN_EXTENT: f32 = 1e-4;
+
+/// An axis-aligned bounding box, stored as one interval per axis.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval<f32>,
+    pub y: Interval<f32>,
+    pub z: Interval<f32>,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+
+    pub fn new(x: Interval<f32>, y: Interval<f32>, z: Interval<f32>) -> Self {
+        Aabb {
+            x: Self::pad(x),
+            y: Self::pad(y),
+            z: Self::pad(z),
+        }
+    }
+
+    pub fn from_points(a: &Vec3, b: &Vec3) -> Self {
+        Aabb::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn from_point_cloud<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        points.into_iter().fold(Aabb::EMPTY, |bbox, point| {
+            bbox.union(&Aabb::from_points(point, point))
+        })
+    }
+
+    fn pad(interval: Interval<f32>) -> Interval<f32> {
+        if interval.size() < MIN_EXTENT {
+            interval.expand(MIN_EXTENT)
+        } else {
+            interval
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty() || self.y.is_empty() || self.z.is_empty()
+    }
+
+    pub fn axis(&self, axis: usize) -> &Interval<f32> {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            x: self.x.hull(&other.x),
+            y: self.y.hull(&other.y),
+            z: self.z.hull(&other.z),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        Vec3::new(
+            (self.x.min + self.x.max) * 0.5,
+            (self.y.min + self.y.max) * 0.5,
+            (self.z.min + self.z.max) * 0.5,
+        )
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool {
+        self.x.contains(point.x) && self.y.contains(point.y) && self.z.contains(point.z)
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.x.overlaps(&other.x) && self.y.overlaps(&other.y) && self.z.overlaps(&other.z)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Slab test: clips the ray interval against each axis in turn and reports whether
+    /// anything is left.
+    pub fn hit(&self, r: &Ray, interval: &Interval<f32>) -> bool {
+        let mut t_min = interval.min;
+        let mut t_max = interval.max;
+        for axis in 0..3 {
+            let slab = self.axis(axis);
+            let inv_d = 1.0 / r.direction[axis];
+            let mut t0 = (slab.min - r.origin[axis]) * inv_d;
+            let mut t1 = (slab.max - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            // Far from the origin the padded slab of a flat box can round to a single
+            // value of t, so an empty-looking overlap still counts as a hit.
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Aabb;
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn aabb_slab_test() {
+        let bbox = Aabb::from_points(&Vec3::new(-1.0, -1.0, -1.0), &Vec3::new(1.0, 1.0, 1.0));
+        let interval = Interval::new(0.0, f32::INFINITY);
+        let towards = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let away = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        let beside = Ray::new(Vec3::new(2.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(bbox.hit(&towards, &interval));
+        assert!(!bbox.hit(&away, &interval));
+        assert!(!bbox.hit(&beside, &interval));
+        assert!(!bbox.hit(&towards, &Interval::new(0.0, 3.0)));
+    }
+
+    #[test]
+    fn aabb_union_and_surface_area() {
+        let a = Aabb::from_points(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(1.0, 1.0, 1.0));
+        let b = Aabb::from_points(&Vec3::new(1.0, 0.0, 0.0), &Vec3::new(2.0, 1.0, 1.0));
+        let both = a.union(&b);
+        assert_eq!(both.surface_area(), 10.0);
+        assert_eq!(Aabb::EMPTY.union(&a).surface_area(), a.surface_area());
+        assert_eq!(Aabb::EMPTY.surface_area(), 0.0);
+        assert!(both.contains(&Vec3::new(1.5, 0.5, 0.5)));
+        assert!(a.overlaps(&b));
+    }
+
+    #[test]
+    fn aabb_pads_flat_boxes() {
+        let flat = Aabb::from_points(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(1.0, 1.0, 0.0));
+        let r = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(flat.z.size() > 0.0);
+        assert!(flat.hit(&r, &Interval::new(0.0, f32::INFINITY)));
+
+        let wall = Aabb::from_points(&Vec3::new(0.0, 0.0, 555.0), &Vec3::new(555.0, 555.0, 555.0));
+        let r = Ray::new(Vec3::new(278.0, 278.0, -800.0), Vec3::new(0.0, 0.0, 1355.0));
+        assert!(wall.hit(&r, &Interval::new(0.001, f32::INFINITY)));
+    }
+}
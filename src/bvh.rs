@@ -0,0 +1,263 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+const BUCKET_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// A bounding-volume hierarchy over a list of hittables.
+///
+/// The tree is built top-down, choosing each split with a binned surface-area heuristic,
+/// and is stored as a flat list of nodes. The objects are reordered so that every leaf
+/// refers to a contiguous run of them.
+pub struct Bvh {
+    objects: Vec<Box<dyn Hittable>>,
+    nodes: Vec<BvhNode>,
+}
+
+struct BvhNode {
+    bbox: Aabb,
+    kind: BvhNodeKind,
+}
+
+enum BvhNodeKind {
+    Leaf { start: usize, count: usize },
+    Interior { left: usize, right: usize },
+}
+
+struct BuildItem {
+    index: usize,
+    bbox: Aabb,
+    centroid: [f32; 3],
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    count: usize,
+    bbox: Aabb,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        let mut items: Vec<BuildItem> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                let centroid = bbox.centroid();
+                BuildItem {
+                    index,
+                    bbox,
+                    centroid: [centroid.x, centroid.y, centroid.z],
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            Self::build(&mut nodes, &mut items, 0);
+        }
+
+        let mut slots: Vec<Option<Box<dyn Hittable>>> = objects.into_iter().map(Some).collect();
+        let objects = items
+            .iter()
+            .map(|item| slots[item.index].take().unwrap())
+            .collect();
+
+        Bvh { objects, nodes }
+    }
+
+    fn build(nodes: &mut Vec<BvhNode>, items: &mut [BuildItem], start: usize) -> usize {
+        let bbox = Self::bounds(items.iter().map(|item| &item.bbox));
+        let node_index = nodes.len();
+        nodes.push(BvhNode {
+            bbox,
+            kind: BvhNodeKind::Leaf {
+                start,
+                count: items.len(),
+            },
+        });
+        if items.len() <= 1 {
+            return node_index;
+        }
+
+        let mut centroid_min = items[0].centroid;
+        let mut centroid_max = items[0].centroid;
+        for item in items.iter() {
+            for axis in 0..3 {
+                centroid_min[axis] = centroid_min[axis].min(item.centroid[axis]);
+                centroid_max[axis] = centroid_max[axis].max(item.centroid[axis]);
+            }
+        }
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                (centroid_max[a] - centroid_min[a]).total_cmp(&(centroid_max[b] - centroid_min[b]))
+            })
+            .unwrap();
+        let extent = centroid_max[axis] - centroid_min[axis];
+        if extent <= 0.0 {
+            // Every centroid coincides, so no split can separate them.
+            return node_index;
+        }
+
+        let bucket_of = |item: &BuildItem| {
+            let offset = (item.centroid[axis] - centroid_min[axis]) / extent;
+            ((offset * BUCKET_COUNT as f32) as usize).min(BUCKET_COUNT - 1)
+        };
+        let mut buckets = [Bucket {
+            count: 0,
+            bbox: Aabb::EMPTY,
+        }; BUCKET_COUNT];
+        for item in items.iter() {
+            let bucket = &mut buckets[bucket_of(item)];
+            bucket.count += 1;
+            bucket.bbox = bucket.bbox.union(&item.bbox);
+        }
+
+        // Cost of splitting after each bucket, relative to the cost of intersecting one
+        // object, weighted by the probability of a ray entering each side.
+        let parent_area = bbox.surface_area().max(f32::EPSILON);
+        let (best_split, best_cost) = (1..BUCKET_COUNT)
+            .map(|split| {
+                let (left, right) = buckets.split_at(split);
+                let cost = TRAVERSAL_COST
+                    + (Self::bucket_cost(left) + Self::bucket_cost(right)) / parent_area;
+                (split, cost)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+
+        if items.len() <= MAX_LEAF_SIZE && best_cost >= items.len() as f32 {
+            return node_index;
+        }
+
+        let mut mid = Self::partition(items, |item| bucket_of(item) < best_split);
+        if mid == 0 || mid == items.len() {
+            // The heuristic could not separate the objects, so fall back to a median split.
+            items.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            mid = items.len() / 2;
+        }
+
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let left = Self::build(nodes, left_items, start);
+        let right = Self::build(nodes, right_items, start + mid);
+        nodes[node_index].kind = BvhNodeKind::Interior { left, right };
+        node_index
+    }
+
+    fn bucket_cost(buckets: &[Bucket]) -> f32 {
+        let count: usize = buckets.iter().map(|b| b.count).sum();
+        let bbox = buckets
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, bucket| bbox.union(&bucket.bbox));
+        count as f32 * bbox.surface_area()
+    }
+
+    fn partition(items: &mut [BuildItem], goes_left: impl Fn(&BuildItem) -> bool) -> usize {
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if goes_left(&items[i]) {
+                items.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+
+    fn bounds<'a>(boxes: impl Iterator<Item = &'a Aabb>) -> Aabb {
+        boxes.fold(Aabb::EMPTY, |acc, bbox| acc.union(bbox))
+    }
+}
+
+impl Hittable for Bvh {
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_so_far = interval.max;
+        let mut record: Option<HitRecord> = None;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node
+                .bbox
+                .hit(r, &Interval::new(interval.min, closest_so_far))
+            {
+                continue;
+            }
+            match node.kind {
+                BvhNodeKind::Leaf { start, count } => {
+                    for object in &self.objects[start..start + count] {
+                        let search = Interval::new(interval.min, closest_so_far);
+                        if let Some(hr) = object.intersect(r, &search) {
+                            closest_so_far = hr.impact.t;
+                            record = Some(hr);
+                        }
+                    }
+                }
+                BvhNodeKind::Interior { left, right } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        record
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bbox,
+            None => Aabb::EMPTY,
+        }
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        for object in &self.objects {
+            object.collect_lights(lights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Bvh;
+    use crate::hittable::{Hittable, Sphere};
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::vec3::Vec3;
+
+    fn spheres(sampler: &mut Sampler) -> Vec<Box<dyn Hittable>> {
+        (0..200)
+            .map(|_| {
+                Box::new(Sphere {
+                    center: Vec3::random_in_range(sampler, -10.0, 10.0),
+                    radius: sampler.get_float_in_range(0.1, 1.0),
+                    material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+                }) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bvh_matches_linear_scan() {
+        let mut sampler = Sampler::new(3);
+        let linear = spheres(&mut Sampler::new(11));
+        let bvh = Bvh::new(spheres(&mut Sampler::new(11)));
+        let interval = Interval::new(0.001, f32::INFINITY);
+        for _ in 0..500 {
+            let r = Ray::new(
+                Vec3::random_in_range(&mut sampler, -20.0, 20.0),
+                Vec3::random_unit(&mut sampler),
+            );
+            let expected = linear.hit(&r, &interval).map(|hr| hr.impact.t);
+            let actual = bvh.hit(&r, &interval).map(|hr| hr.impact.t);
+            assert_eq!(expected, actual);
+        }
+    }
+}
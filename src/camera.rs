@@ -1,9 +1,22 @@
-use crate::numeric_utilities;
+use crate::film::Film;
+use crate::integrator::{
+    AmbientOcclusion, DebugChannel, DebugIntegrator, Integrator, IntegratorMode, LightSampling,
+    PathIntegrator,
+};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
 use crate::vec3::Vec3;
-use crate::Hittable;
-use crate::Interval;
-use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuildError;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const TILE_SIZE: u32 = 16;
+
+/// The largest image width or height a camera will render.
+pub const MAX_IMAGE_SIZE: u32 = 1 << 16;
 
 pub struct Camera {
     image_width: u32,
@@ -16,9 +29,63 @@ pub struct Camera {
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     pixel_samples: u32,
-    max_ray_depth: u32,
+    integrator: Box<dyn Integrator>,
+    threads: usize,
+    seed: u64,
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    ThreadPool(ThreadPoolBuildError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::ThreadPool(source) => {
+                write!(f, "failed to create the render thread pool: {}", source)
+            }
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::ThreadPool(source) => Some(source),
+        }
+    }
+}
+
+/// A camera setting that can't give an image, with the value it was given and what was
+/// expected instead.
+#[derive(Debug)]
+pub struct CameraError {
+    pub setting: &'static str,
+    pub value: String,
+    pub expected: &'static str,
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} {}: expected {}",
+            self.setting, self.value, self.expected
+        )
+    }
+}
+
+impl Error for CameraError {}
+
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
 }
 
+#[derive(Clone)]
 pub struct CameraBuilder {
     image_width: u32,
     aspect_ratio: f32,
@@ -30,6 +97,10 @@ pub struct CameraBuilder {
     v_up: Vec3,
     pixel_samples: u32,
     max_ray_depth: u32,
+    roulette_depth: u32,
+    integrator: IntegratorMode,
+    threads: usize,
+    seed: u64,
 }
 
 impl CameraBuilder {
@@ -45,6 +116,10 @@ impl CameraBuilder {
             v_up: Vec3::new(0.0, 1.0, 0.0),
             pixel_samples: 500,
             max_ray_depth: 50,
+            roulette_depth: 5,
+            integrator: IntegratorMode::default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: 0,
         }
     }
 
@@ -98,23 +173,162 @@ impl CameraBuilder {
         self
     }
 
-    pub fn build(self) -> Camera {
-        Camera::new(
-            self.image_width,
+    /// The number of bounces after which paths may be ended by Russian roulette. Set it
+    /// to the maximum ray depth to turn roulette off.
+    pub fn roulette_depth(mut self, roulette_depth: u32) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: IntegratorMode) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Fails if a setting can't give an image, such as a zero width, an aspect ratio that
+    /// isn't a positive number or one that makes the image taller than
+    /// [`MAX_IMAGE_SIZE`].
+    pub fn build(self) -> Result<Camera, CameraError> {
+        self.validate()?;
+        let integrator = self.build_integrator();
+        let image_height = 1.max((self.image_width as f32 / self.aspect_ratio) as u32);
+
+        let theta = self.vert_fov.to_radians();
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0f32 * self.focal_dist * h;
+        let viewport_width = viewport_height * (self.image_width as f32 / image_height as f32);
+
+        let w = (&self.look_from - &self.look_at).unit_vector();
+        let u = self.v_up.cross(&w).unit_vector();
+        let v = w.cross(&u);
+        let center = self.look_from;
+
+        let viewport_u = &u * viewport_width;
+        let viewport_v = &v * -viewport_height;
+
+        let pixel_delta_u = &viewport_u / self.image_width as f32;
+        let pixel_delta_v = &viewport_v / image_height as f32;
+
+        let viewport_upper_left =
+            &center - (w * self.focal_dist) - &viewport_u / 2.0f32 - &viewport_v / 2.0f32;
+        let pixel00_loc = &viewport_upper_left + ((&pixel_delta_u + &pixel_delta_v) * 0.5f32);
+
+        let defocus_radius = self.focal_dist * (self.defocus_angle / 2.0).to_radians().tan();
+        let defocus_disk_u = u * defocus_radius;
+        let defocus_disk_v = v * defocus_radius;
+
+        Ok(Camera {
+            image_width: self.image_width,
+            image_height,
+            center,
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            defocus_angle: self.defocus_angle,
+            defocus_disk_u,
+            defocus_disk_v,
+            pixel_samples: self.pixel_samples,
+            integrator,
+            threads: self.threads,
+            seed: self.seed,
+        })
+    }
+}
+
+impl CameraBuilder {
+    fn validate(&self) -> Result<(), CameraError> {
+        let check = |setting, value: f32, is_valid: bool, expected| {
+            if is_valid {
+                Ok(())
+            } else {
+                Err(CameraError {
+                    setting,
+                    value: value.to_string(),
+                    expected,
+                })
+            }
+        };
+        let size = MAX_IMAGE_SIZE as f32;
+        let width = self.image_width as f32;
+        check(
+            "image width",
+            width,
+            self.image_width > 0 && self.image_width <= MAX_IMAGE_SIZE,
+            "a whole number from 1 to 65536",
+        )?;
+        check(
+            "aspect ratio",
+            self.aspect_ratio,
+            self.aspect_ratio > 0.0 && self.aspect_ratio.is_finite(),
+            "a number above zero",
+        )?;
+        check(
+            "aspect ratio",
             self.aspect_ratio,
+            width / self.aspect_ratio <= size,
+            "a ratio giving an image at most 65536 pixels high",
+        )?;
+        check(
+            "vertical field of view",
             self.vert_fov,
+            self.vert_fov > 0.0 && self.vert_fov < 180.0,
+            "an angle between 0 and 180 degrees",
+        )?;
+        check(
+            "defocus angle",
             self.defocus_angle,
+            self.defocus_angle >= 0.0 && self.defocus_angle < 180.0,
+            "an angle from 0 up to 180 degrees",
+        )?;
+        check(
+            "focus distance",
             self.focal_dist,
-            self.look_from,
-            self.look_at,
-            self.v_up,
-            self.pixel_samples,
-            self.max_ray_depth,
+            self.focal_dist > 0.0 && self.focal_dist.is_finite(),
+            "a number above zero",
+        )?;
+        check(
+            "pixel samples",
+            self.pixel_samples as f32,
+            self.pixel_samples > 0,
+            "at least one sample",
         )
     }
+
+    fn build_integrator(&self) -> Box<dyn Integrator> {
+        let path = |light_sampling| {
+            Box::new(PathIntegrator {
+                light_sampling,
+                max_ray_depth: self.max_ray_depth,
+                roulette_depth: self.roulette_depth,
+            })
+        };
+        match self.integrator {
+            IntegratorMode::Path => path(LightSampling::None),
+            IntegratorMode::Nee => path(LightSampling::Nee),
+            IntegratorMode::Mis => path(LightSampling::Mis),
+            IntegratorMode::Normals => Box::new(DebugIntegrator {
+                channel: DebugChannel::Normals,
+            }),
+            IntegratorMode::Albedo => Box::new(DebugIntegrator {
+                channel: DebugChannel::Albedo,
+            }),
+            IntegratorMode::Ao => Box::new(AmbientOcclusion { distance: None }),
+        }
+    }
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         image_width: u32,
         aspect_ratio: f32,
@@ -126,123 +340,272 @@ impl Camera {
         v_up: Vec3,
         pixel_samples: u32,
         max_ray_depth: u32,
-    ) -> Self {
-        let image_height = 1.max((image_width as f32 / aspect_ratio) as u32);
-
-        let theta = vert_fov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0f32 * focal_dist * h;
-        let viewport_width = viewport_height * (image_width as f32 / image_height as f32);
-
-        let w = (&look_from - &look_at).unit_vector();
-        let u = v_up.cross(&w).unit_vector();
-        let v = w.cross(&u);
-        let center = look_from;
+    ) -> Result<Self, CameraError> {
+        CameraBuilder::new(image_width, aspect_ratio)
+            .vert_fov(vert_fov)
+            .defocus_angle(defocus_angle)
+            .focal_dist(focal_dist)
+            .look_from(look_from)
+            .look_at(look_at)
+            .v_up(v_up)
+            .pixel_samples(pixel_samples)
+            .max_ray_depth(max_ray_depth)
+            .build()
+    }
 
-        let viewport_u = &u * viewport_width;
-        let viewport_v = &v * (viewport_height * -1.0);
+    /// Swaps in an integrator of your own in place of the one the builder chose.
+    pub fn with_integrator(mut self, integrator: impl Integrator + 'static) -> Self {
+        self.integrator = Box::new(integrator);
+        self
+    }
 
-        let pixel_delta_u = &viewport_u / image_width as f32;
-        let pixel_delta_v = &viewport_v / image_height as f32;
+    pub fn render(&self, scene: &Scene) -> Result<Film, RenderError> {
+        self.render_with_progress(scene, |_, _| {})
+    }
 
-        let viewport_upper_left =
-            &center - (w * focal_dist) - &viewport_u / 2.0f32 - &viewport_v / 2.0f32;
-        let pixel00_loc = &viewport_upper_left + ((&pixel_delta_u + &pixel_delta_v) * 0.5f32);
+    /// Renders the image, calling `progress` with the number of finished tiles and the
+    /// total number of tiles each time a tile completes.
+    pub fn render_with_progress(
+        &self,
+        scene: &Scene,
+        progress: impl Fn(usize, usize) + Sync,
+    ) -> Result<Film, RenderError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .map_err(RenderError::ThreadPool)?;
+        let tiles = self.tiles();
+        let finished = AtomicUsize::new(0);
+        let rendered: Vec<Vec<Vec3>> = pool.install(|| {
+            tiles
+                .par_iter()
+                .map(|tile| {
+                    let pixels = self.render_tile(scene, tile);
+                    progress(finished.fetch_add(1, Ordering::Relaxed) + 1, tiles.len());
+                    pixels
+                })
+                .collect()
+        });
 
-        let defocus_radius = focal_dist * (defocus_angle / 2.0).to_radians().tan();
-        let defocus_disk_u = u * defocus_radius;
-        let defocus_disk_v = v * defocus_radius;
+        let mut film = Film::new(self.image_width, self.image_height);
+        for (tile, pixels) in tiles.iter().zip(rendered) {
+            for (i, radiance) in pixels.iter().enumerate() {
+                let x = tile.x + i as u32 % tile.width;
+                let y = tile.y + i as u32 / tile.width;
+                film.put_pixel(x, y, radiance)
+            }
+        }
+        Ok(film)
+    }
 
-        Camera {
-            image_width,
-            image_height,
-            center,
-            pixel00_loc,
-            pixel_delta_u,
-            pixel_delta_v,
-            defocus_angle,
-            defocus_disk_u,
-            defocus_disk_v,
-            pixel_samples,
-            max_ray_depth,
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y in (0..self.image_height).step_by(TILE_SIZE as usize) {
+            for x in (0..self.image_width).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: TILE_SIZE.min(self.image_width - x),
+                    height: TILE_SIZE.min(self.image_height - y),
+                });
+            }
         }
+        tiles
     }
 
-    pub fn render(&self, world: &Vec<Box<dyn Hittable>>) -> RgbImage {
-        let mut image = RgbImage::new(self.image_width, self.image_height);
-        for y in 0..self.image_height {
-            for x in 0..self.image_width {
-                let pixel = self.render_point(world, x, y);
-                image.put_pixel(x, y, pixel)
+    fn render_tile(&self, scene: &Scene, tile: &Tile) -> Vec<Vec3> {
+        let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                pixels.push(self.render_point(scene, x, y));
             }
         }
-        image
+        pixels
     }
 
-    pub fn render_point(&self, world: &Vec<Box<dyn Hittable>>, x: u32, y: u32) -> Rgb<u8> {
-        let hit_interval = Interval::<f32>::new(0.001, f32::INFINITY);
-        let clamp_interval = Interval::<f32>::new(0.0, 0.999);
+    /// The average linear radiance arriving at a pixel.
+    pub fn render_point(&self, scene: &Scene, x: u32, y: u32) -> Vec3 {
+        let mut sampler = Sampler::for_pixel(self.seed, x, y);
         let mut running_colour = Vec3::new(0.0, 0.0, 0.0);
         for _ in 0..self.pixel_samples {
-            let r = self.get_ray(x, y);
-            let colour = Self::get_ray_color(&r, world, &hit_interval, self.max_ray_depth);
+            let r = self.get_ray(x, y, &mut sampler);
+            let colour = self.integrator.radiance(&r, scene, &mut sampler);
             running_colour += colour / self.pixel_samples as f32;
         }
-        Rgb([
-            (clamp_interval.clamp(&numeric_utilities::linear_to_gamma(running_colour.x)) * 256f32)
-                as u8,
-            (clamp_interval.clamp(&numeric_utilities::linear_to_gamma(running_colour.y)) * 256f32)
-                as u8,
-            (clamp_interval.clamp(&numeric_utilities::linear_to_gamma(running_colour.z)) * 256f32)
-                as u8,
-        ])
+        running_colour
     }
 
-    fn get_ray(&self, x: u32, y: u32) -> Ray {
+    fn get_ray(&self, x: u32, y: u32, sampler: &mut Sampler) -> Ray {
         let mut pixel_center =
             &self.pixel00_loc + &(&self.pixel_delta_u * x as f32 + &self.pixel_delta_v * y as f32);
-        pixel_center += &self.pixel_delta_u * (-0.5 + numeric_utilities::get_rand_float());
-        pixel_center += &self.pixel_delta_v * (-0.5 + numeric_utilities::get_rand_float());
+        pixel_center += &self.pixel_delta_u * (-0.5 + sampler.get_float());
+        pixel_center += &self.pixel_delta_v * (-0.5 + sampler.get_float());
 
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center.clone()
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
 
         let ray_direction = &pixel_center - &ray_origin;
         Ray::new(ray_origin, ray_direction)
     }
 
-    fn get_ray_color(
-        r: &Ray,
-        world: &Vec<Box<dyn Hittable>>,
-        hit_interval: &Interval<f32>,
-        remaining_ray_depth: u32,
-    ) -> Vec3 {
-        if remaining_ray_depth == 0 {
-            Vec3::new(0.0, 0.0, 0.0)
-        } else if let Some(hr) = world.hit(r, hit_interval) {
-            if let Some(scattering) = hr.scattered {
-                scattering.attenuation
-                    * Self::get_ray_color(
-                        &scattering.scattered,
-                        world,
-                        hit_interval,
-                        remaining_ray_depth - 1,
-                    )
-            } else {
-                Vec3::new(0.0, 0.0, 0.0)
-            }
-        } else {
-            let unit_direction = r.direction.unit_vector();
-            let a = 0.5 * (unit_direction.y + 1.0);
-            Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a
+    fn defocus_disk_sample(&self, sampler: &mut Sampler) -> Vec3 {
+        let r = Vec3::random_in_unit_disk(sampler);
+        &self.center + (&self.defocus_disk_u * r.x) + (&self.defocus_disk_v * r.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::CameraBuilder;
+    use crate::environment::SolidColor;
+    use crate::hittable::{Hittable, Sphere};
+    use crate::integrator::IntegratorMode;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal};
+    use crate::scene::Scene;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn render_is_independent_of_thread_count() {
+        let world: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Sphere {
+                center: Vec3::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+                material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+            }),
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 0.0, -1.0),
+                radius: 0.5,
+                material: Dielectric::new(1.5),
+            }),
+        ];
+        let builder = CameraBuilder::new(40, 16.0 / 9.0)
+            .pixel_samples(4)
+            .max_ray_depth(8)
+            .seed(7);
+        let scene = Scene::new(world);
+        let single = builder
+            .clone()
+            .threads(1)
+            .build()
+            .unwrap()
+            .render(&scene)
+            .unwrap();
+        let multi = builder.threads(4).build().unwrap().render(&scene).unwrap();
+        assert_eq!(single.as_image().as_raw(), multi.as_image().as_raw());
+    }
+
+    #[test]
+    fn build_rejects_settings_that_give_no_image() {
+        let builder = CameraBuilder::new(16, 1.0);
+        assert!(builder.clone().build().is_ok());
+        for aspect_ratio in [0.0, -1.0, f32::NAN, f32::INFINITY, 1e-9] {
+            let error = builder
+                .clone()
+                .aspect_ratio(aspect_ratio)
+                .build()
+                .err()
+                .unwrap();
+            assert_eq!(error.setting, "aspect ratio");
         }
+        let error = builder.clone().image_width(0).build().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "invalid image width 0: expected a whole number from 1 to 65536"
+        );
+        assert!(builder.clone().pixel_samples(0).build().is_err());
+        assert!(builder.vert_fov(180.0).build().is_err());
     }
 
-    fn defocus_disk_sample(&self) -> Vec3 {
-        let r = Vec3::random_in_unit_disk();
-        &self.center + (&self.defocus_disk_u * r.x) + (&self.defocus_disk_v * r.y)
+    #[test]
+    fn emitters_are_seen_directly() {
+        // The camera looks down -z into a light big enough to fill the whole view.
+        let scene = Scene::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -20.0),
+            radius: 10.0,
+            material: DiffuseLight::new(Vec3::new(4.0, 2.0, 1.0)),
+        });
+        let film = CameraBuilder::new(8, 1.0)
+            .pixel_samples(2)
+            .defocus_angle(0.0)
+            .build()
+            .unwrap()
+            .render(&scene)
+            .unwrap();
+        let pixel = film.get_pixel(4, 4);
+        assert_eq!((pixel.x, pixel.y, pixel.z), (4.0, 2.0, 1.0));
+    }
+
+    /// A metal and a diffuse sphere lit only by a spherical light above them.
+    fn lit_scene() -> Scene {
+        let world: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Sphere {
+                center: Vec3::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+                material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+            }),
+            Box::new(Sphere {
+                center: Vec3::new(0.6, -0.2, -1.0),
+                radius: 0.3,
+                material: Metal::new(Vec3::new(0.8, 0.8, 0.8), 0.2),
+            }),
+            Box::new(Sphere {
+                center: Vec3::new(0.0, 1.5, -1.0),
+                radius: 1.0,
+                material: DiffuseLight::new(Vec3::new(4.0, 4.0, 4.0)),
+            }),
+        ];
+        Scene::new(world).with_environment(SolidColor::new(Vec3::new(0.0, 0.0, 0.0)))
+    }
+
+    /// A small pinhole view of [`lit_scene`].
+    fn lit_scene_camera() -> CameraBuilder {
+        CameraBuilder::new(16, 1.0)
+            .look_from(Vec3::new(0.0, 0.0, 2.0))
+            .look_at(Vec3::new(0.0, -0.5, -1.0))
+            .defocus_angle(0.0)
+            .pixel_samples(256)
+    }
+
+    /// The mean red value of the image `builder` renders of `scene`.
+    fn mean_red(builder: CameraBuilder, scene: &Scene) -> f32 {
+        let film = builder.build().unwrap().render(scene).unwrap();
+        let total: f32 = film.as_image().pixels().map(|p| p[0]).sum();
+        total / (film.width() * film.height()) as f32
+    }
+
+    #[test]
+    fn integrators_agree() {
+        let scene = lit_scene();
+        let builder = lit_scene_camera().max_ray_depth(4);
+        let mean = |integrator| mean_red(builder.clone().integrator(integrator), &scene);
+        let path = mean(IntegratorMode::Path);
+        for integrator in [IntegratorMode::Nee, IntegratorMode::Mis] {
+            let sampled = mean(integrator);
+            assert!(
+                (sampled - path).abs() < 0.03 * path,
+                "{integrator}: {sampled} vs {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn russian_roulette_is_unbiased() {
+        let scene = lit_scene();
+        let builder = lit_scene_camera()
+            .pixel_samples(1024)
+            .max_ray_depth(16)
+            .integrator(IntegratorMode::Path);
+        let mean =
+            |roulette_depth| mean_red(builder.clone().roulette_depth(roulette_depth), &scene);
+        let full = mean(16);
+        let roulette = mean(1);
+        assert!(
+            (roulette - full).abs() < 0.02 * full,
+            "{roulette} vs {full}"
+        );
     }
 }
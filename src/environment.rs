@@ -0,0 +1,222 @@
+//! What a ray sees when it leaves the scene without hitting anything.
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::hdr::HdrDecoder;
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+use image::{ImageResult, Rgb32FImage};
+
+use crate::vec3::Vec3;
+
+/// Light arriving from infinitely far away, looked up by direction.
+pub trait Environment: Send + Sync {
+    /// The radiance arriving along the reverse of `direction`, which need not be a unit
+    /// vector.
+    fn radiance(&self, direction: &Vec3) -> Vec3;
+}
+
+/// The same radiance from every direction. Black turns the environment off, for closed
+/// interiors lit only by area lights.
+pub struct SolidColor {
+    color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(color: Vec3) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Environment for SolidColor {
+    fn radiance(&self, _direction: &Vec3) -> Vec3 {
+        self.color.clone()
+    }
+}
+
+/// A vertical blend from `bottom` straight down to `top` straight up. The default is the
+/// white-to-blue sky of Ray Tracing in One Weekend.
+pub struct Gradient {
+    bottom: Vec3,
+    top: Vec3,
+}
+
+impl Gradient {
+    pub fn new(bottom: Vec3, top: Vec3) -> Self {
+        Gradient { bottom, top }
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let a = 0.5 * (direction.unit_vector().y + 1.0);
+        &self.bottom * (1.0 - a) + &self.top * a
+    }
+}
+
+/// A latitude-longitude (equirectangular) radiance map, usually loaded from an HDR image.
+///
+/// The centre of the image lies along -z, the top row is straight up, and `rotation`
+/// turns the map about the y axis.
+pub struct EnvironmentMap {
+    image: Rgb32FImage,
+    rotation: f32,
+    intensity: f32,
+}
+
+impl EnvironmentMap {
+    /// An empty image is replaced by a single black texel, which turns the environment off.
+    pub fn new(image: Rgb32FImage) -> Self {
+        let image = if image.width() == 0 || image.height() == 0 {
+            Rgb32FImage::new(1, 1)
+        } else {
+            image
+        };
+        EnvironmentMap {
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    /// Loads any format the `image` crate can read, though only `.hdr` and `.exr` keep
+    /// radiance above one. Empty images are rejected.
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        let image = open_linear(path.as_ref())?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        Ok(EnvironmentMap::new(image))
+    }
+
+    /// Turns the map about the y axis by `degrees`.
+    pub fn rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    /// Scales the radiance of every texel.
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let width = self.image.width() as i64;
+        let height = self.image.height() as i64;
+        let pixel = self
+            .image
+            .get_pixel(x.rem_euclid(width) as u32, y.clamp(0, height - 1) as u32);
+        Vec3::new(pixel[0], pixel[1], pixel[2])
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let d = direction.unit_vector();
+        let phi = d.x.atan2(-d.z) + self.rotation;
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let u = 0.5 + phi / (2.0 * PI);
+        let v = theta / PI;
+
+        // Bilinear filtering, wrapping around horizontally.
+        let x = u * self.image.width() as f32 - 0.5;
+        let y = v * self.image.height() as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let upper = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let lower = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        (upper * (1.0 - fy) + lower * fy) * self.intensity
+    }
+}
+
+/// Opens an image as linear floats. The `image` crate reads Radiance HDR files as 8-bit
+/// colour, so those are decoded here instead to keep radiance above one.
+pub(crate) fn open_linear(path: &Path) -> ImageResult<Rgb32FImage> {
+    let is_hdr = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("hdr"));
+    if !is_hdr {
+        return Ok(image::open(path)?.into_rgb32f());
+    }
+    let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+    let metadata = decoder.metadata();
+    let raw = decoder
+        .read_image_hdr()?
+        .iter()
+        .flat_map(|pixel| pixel.0)
+        .collect();
+    Rgb32FImage::from_raw(metadata.width, metadata.height, raw).ok_or_else(|| {
+        ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::DimensionMismatch,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Environment, EnvironmentMap, Gradient};
+    use crate::film::Film;
+    use crate::vec3::Vec3;
+    use image::{Rgb, Rgb32FImage};
+    use std::fs;
+
+    #[test]
+    fn environment_map_looks_up_by_direction() {
+        // Left half red, right half blue, so -x is red and +x is blue.
+        let image = Rgb32FImage::from_fn(64, 32, |x, _| {
+            if x < 32 {
+                Rgb([1.0, 0.0, 0.0])
+            } else {
+                Rgb([0.0, 0.0, 1.0])
+            }
+        });
+        let map = EnvironmentMap::new(image).intensity(2.0);
+        let left = map.radiance(&Vec3::new(-1.0, 0.0, 0.0));
+        let right = map.radiance(&Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!((left.x, left.z), (2.0, 0.0));
+        assert_eq!((right.x, right.z), (0.0, 2.0));
+
+        let turned = EnvironmentMap::new(Rgb32FImage::from_pixel(4, 2, Rgb([1.0, 1.0, 1.0])))
+            .rotation(90.0)
+            .radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(turned.y, 1.0);
+
+        let empty =
+            EnvironmentMap::new(Rgb32FImage::new(0, 0)).radiance(&Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!((empty.x, empty.y, empty.z), (0.0, 0.0, 0.0));
+
+        let sky = Gradient::default().radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!((sky.x, sky.y, sky.z), (0.5, 0.7, 1.0));
+    }
+
+    #[test]
+    fn hdr_environment_maps_keep_bright_radiance() {
+        let mut film = Film::new(4, 2);
+        for y in 0..2 {
+            for x in 0..4 {
+                film.put_pixel(x, y, &Vec3::new(8.0, 4.0, 0.5));
+            }
+        }
+        let path =
+            std::env::temp_dir().join(format!("rust_tracing_sky_{}.hdr", std::process::id()));
+        film.save_hdr(&path).unwrap();
+        let map = EnvironmentMap::load(&path);
+        fs::remove_file(&path).unwrap();
+        let sky = map.unwrap().radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!((sky.x, sky.y, sky.z), (8.0, 4.0, 0.5));
+    }
+}
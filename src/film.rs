@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageResult, Rgb, Rgb32FImage, RgbImage};
+
+use crate::numeric_utilities;
+use crate::tonemap::ToneMapping;
+use crate::vec3::Vec3;
+
+/// A linear, high-dynamic-range RGB image that the camera renders into.
+///
+/// Radiance is kept as unclamped `f32`s so it can be written out as OpenEXR or Radiance
+/// HDR; quantising to 8 bits per channel is a separate, optional step.
+pub struct Film {
+    image: Rgb32FImage,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Film {
+            image: Rgb32FImage::new(width, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, radiance: &Vec3) {
+        self.image
+            .put_pixel(x, y, Rgb([radiance.x, radiance.y, radiance.z]));
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Vec3 {
+        let Rgb([r, g, b]) = *self.image.get_pixel(x, y);
+        Vec3::new(r, g, b)
+    }
+
+    pub fn as_image(&self) -> &Rgb32FImage {
+        &self.image
+    }
+
+    /// Tone maps the film and encodes it as an 8-bit sRGB image.
+    pub fn to_rgb8(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        let quantise =
+            |linear: f32| (numeric_utilities::linear_to_srgb(linear) * 255.0).round() as u8;
+        RgbImage::from_fn(self.width(), self.height(), |x, y| {
+            let display = tone_mapping.apply(&self.get_pixel(x, y));
+            Rgb([
+                quantise(display.x),
+                quantise(display.y),
+                quantise(display.z),
+            ])
+        })
+    }
+
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        self.image
+            .save_with_format(path, image::ImageFormat::OpenExr)
+    }
+
+    pub fn save_hdr(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let file = BufWriter::new(File::create(path)?);
+        HdrEncoder::new(file).encode(
+            self.image.pixels().copied().collect::<Vec<_>>().as_slice(),
+            self.width() as usize,
+            self.height() as usize,
+        )
+    }
+
+    /// Saves the film in the format given by the extension of `path`. `.exr` and `.hdr`
+    /// keep the full dynamic range; anything else is tone mapped and quantised with
+    /// [`Film::to_rgb8`].
+    pub fn save(&self, path: impl AsRef<Path>, tone_mapping: &ToneMapping) -> ImageResult<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("exr") => self.save_exr(path),
+            Some("hdr") => self.save_hdr(path),
+            _ => self.to_rgb8(tone_mapping).save(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Film;
+    use crate::environment::open_linear;
+    use crate::tonemap::ToneMapping;
+    use crate::vec3::Vec3;
+    use std::fs;
+
+    #[test]
+    fn high_dynamic_range_files_keep_linear_values() {
+        let mut film = Film::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let scale = (1 + x + 3 * y) as f32;
+                film.put_pixel(x, y, &Vec3::new(0.25 * scale, 1.5 * scale, 6.0 * scale));
+            }
+        }
+        // Radiance HDR shares an 8-bit mantissa's exponent between the channels.
+        for (extension, tolerance) in [("exr", 0.0), ("hdr", 1.0 / 128.0)] {
+            let path = std::env::temp_dir().join(format!(
+                "rust_tracing_film_{}.{}",
+                std::process::id(),
+                extension
+            ));
+            film.save(&path, &ToneMapping::default()).unwrap();
+            let read = open_linear(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(read.dimensions(), (3, 2));
+            for (x, y, pixel) in read.enumerate_pixels() {
+                let expected = film.get_pixel(x, y);
+                let largest = expected.x.max(expected.y).max(expected.z);
+                for (channel, value) in [expected.x, expected.y, expected.z].iter().enumerate() {
+                    assert!(
+                        (pixel[channel] - value).abs() <= tolerance * largest,
+                        "{extension} ({x}, {y})[{channel}]: {} vs {value}",
+                        pixel[channel]
+                    );
+                }
+            }
+        }
+    }
+}
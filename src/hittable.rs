@@ -1,44 +1,172 @@
+use std::f32::consts::PI;
+
+use crate::aabb::Aabb;
 use crate::interval::Interval;
 use crate::material::{Material, Scattering};
+use crate::onb::Onb;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::Vec3;
 
+#[derive(Clone)]
 pub struct Impact {
     pub point: Vec3,
+    /// The normal materials shade with, which may be interpolated or perturbed. It is
+    /// always on the same side of the surface as `geometric_normal`.
     pub normal: Vec3,
+    /// The true normal of the surface, facing against the ray.
+    pub geometric_normal: Vec3,
     pub t: f32,
     pub is_front_face: bool,
+    /// Weights of the three vertices at the hit point, for hits on triangles.
+    pub barycentric: Option<Vec3>,
+    /// Surface coordinates of the hit point, for looking up textures.
+    pub uv: (f32, f32),
+    /// How the hit point moves as `u` and `v` increase.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// A basis around `normal` with its `u` axis along `dpdu`, in which materials can
+    /// work in tangent space. Only `w` is set until [`Impact::with_frame`] is called.
+    pub frame: Onb,
 }
 
-pub struct HitRecord {
+/// The closest hit along a ray: where it is, the material to scatter off once the caller
+/// needs to, and the shape that was hit, to fill in the surface detail.
+pub struct HitRecord<'a> {
     pub impact: Impact,
-    pub scattered: Option<Scattering>,
+    pub material: &'a dyn Material,
+    pub shape: &'a dyn Hittable,
 }
 
 impl Impact {
     pub fn new(r: &Ray, point: Vec3, normal: Vec3, t: f32) -> Self {
         let is_front_face = r.direction.dot(&normal) < 0.0;
+        let normal = if is_front_face {
+            normal
+        } else {
+            &normal * -1.0
+        };
         Impact {
             point,
-            normal: if is_front_face {
-                normal
-            } else {
-                &normal * -1.0
+            geometric_normal: normal.clone(),
+            frame: Onb {
+                u: Vec3::default(),
+                v: Vec3::default(),
+                w: normal.clone(),
             },
+            normal,
             t,
             is_front_face,
+            barycentric: None,
+            uv: (0.0, 0.0),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+        }
+    }
+
+    pub fn with_barycentric(mut self, barycentric: Vec3) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    pub fn with_uv(mut self, uv: (f32, f32)) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Replaces the shading normal with an interpolated or perturbed one, turned to the
+    /// same side as the geometric normal so that `is_front_face` still holds.
+    pub fn with_shading_normal(mut self, shading_normal: Vec3) -> Self {
+        self.normal = if shading_normal.dot(&self.geometric_normal) < 0.0 {
+            shading_normal * -1.0
+        } else {
+            shading_normal
+        };
+        self
+    }
+
+    /// Builds `frame` from `normal` and `dpdu`, or around `normal` alone if the shape
+    /// gave no tangents. [`Hittable::hit`] does this for every hit it returns; call it
+    /// again after changing either.
+    pub fn with_frame(mut self) -> Self {
+        self.frame = Onb::from_w_u(&self.normal, &self.dpdu);
+        self
+    }
+}
+
+impl<'a> HitRecord<'a> {
+    pub fn new(impact: Impact, material: &'a dyn Material, shape: &'a dyn Hittable) -> Self {
+        HitRecord {
+            impact,
+            material,
+            shape,
         }
     }
+
+    /// Completes a hit found by [`Hittable::intersect`] with its surface detail, shading
+    /// frame and any shading adjustment from its material, as [`Hittable::hit`] returns it.
+    pub fn shaded(self) -> Self {
+        let impact = self.shape.surface_detail(self.impact).with_frame();
+        let impact = self.material.shade(impact);
+        HitRecord::new(impact, self.material, self.shape)
+    }
 }
 
-impl HitRecord {
-    pub fn new(impact: Impact, scattered: Option<Scattering>) -> HitRecord {
-        HitRecord { impact, scattered }
+pub trait Hittable: Send + Sync {
+    /// The closest hit along `r` within `interval`, with only its position, normal and
+    /// barycentric weights worked out. Collections test their objects with this, so that
+    /// only the hit that ends up closest pays for the rest.
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>>;
+
+    /// Fills in the UVs, tangents and shading normal of a hit on the shape found by
+    /// [`Hittable::intersect`]. Shapes without a parameterisation leave it as it is, which
+    /// is the default.
+    fn surface_detail(&self, impact: Impact) -> Impact {
+        impact
+    }
+
+    /// The closest hit along `r` within `interval`, complete with its surface detail,
+    /// shading frame and any shading adjustment from its material.
+    fn hit(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        self.intersect(r, interval).map(HitRecord::shaded)
+    }
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// The probability density, per unit solid angle, that [`Hittable::random_towards`]
+    /// picks `direction` from `origin`. Shapes that can't be sampled as lights return zero,
+    /// which is the default.
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// A random direction from `origin` towards a point on the shape, or `None` for shapes
+    /// that can't be sampled as lights, which is the default.
+    fn random_towards(&self, _origin: &Vec3, _sampler: &mut Sampler) -> Option<Vec3> {
+        None
     }
+
+    /// Adds the parts of the shape made of an emissive material to `lights`, for the
+    /// integrator to sample directly. Shapes that can't be sampled add nothing, which is the
+    /// default.
+    fn collect_lights(&self, _lights: &mut Vec<Box<dyn Hittable>>) {}
 }
 
-pub trait Hittable {
-    fn hit(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord>;
+/// The material of the copies of shapes gathered by [`Hittable::collect_lights`]. Lights
+/// are only sampled, never hit, so a copy needs the shape of the original but not its
+/// material, which may be expensive or impossible to copy.
+pub(crate) struct ShapeOnly;
+
+impl Material for ShapeOnly {
+    fn sample(&self, _r: &Ray, _impact: &Impact, _sampler: &mut Sampler) -> Option<Scattering> {
+        None
+    }
 }
 
 pub struct Sphere<T> {
@@ -47,11 +175,9 @@ pub struct Sphere<T> {
     pub material: T,
 }
 
-impl<T> Hittable for Sphere<T>
-where
-    T: Material,
-{
-    fn hit(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord> {
+impl<T> Sphere<T> {
+    /// The nearest ray parameter in `interval` at which `r` crosses the sphere.
+    fn root(&self, r: &Ray, interval: &Interval<f32>) -> Option<f32> {
         let oc = &r.origin - &self.center;
         let a = r.direction.length_squared();
         let half_b = oc.dot(&r.direction);
@@ -70,22 +196,109 @@ where
                 return None;
             }
         }
+        Some(root)
+    }
 
+    /// Longitude and latitude on the unit sphere, each mapped to `[0, 1]`, with `u` starting
+    /// from -x and `v` from the south pole.
+    fn uv(normal: &Vec3) -> (f32, f32) {
+        let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+        let phi = (-normal.z).atan2(normal.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// The derivatives of the point with `u` and `v` of the spherical mapping, at the point
+    /// with outward `normal`. At the poles `dpdu` vanishes.
+    fn tangents(&self, normal: &Vec3) -> (Vec3, Vec3) {
+        let (x, y, z) = (normal.x, normal.y, normal.z);
+        let sin_theta = (x * x + z * z).sqrt();
+        let dpdu = Vec3::new(z, 0.0, -x) * (2.0 * PI * self.radius);
+        let dpdv = if sin_theta > 0.0 {
+            Vec3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta) * (PI * self.radius)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+        (dpdu, dpdv)
+    }
+
+    /// The cosine of the half-angle of the cone the sphere fills as seen from `origin`,
+    /// or `None` if `origin` is inside it.
+    fn cos_theta_max(&self, origin: &Vec3) -> Option<f32> {
+        let distance_squared = (&self.center - origin).length_squared();
+        let sin2_theta_max = self.radius * self.radius / distance_squared;
+        (sin2_theta_max < 1.0).then(|| (1.0 - sin2_theta_max).sqrt())
+    }
+}
+
+impl<T> Hittable for Sphere<T>
+where
+    T: Material,
+{
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        let root = self.root(r, interval)?;
         let hit_point = r.at(root);
         let normal = (&hit_point - &self.center) / self.radius;
-
         let impact = Impact::new(r, hit_point, normal, root);
-        let scattered = self.material.scatter(&r, &impact);
-        Some(HitRecord::new(impact, scattered))
+        Some(HitRecord::new(impact, &self.material, self))
+    }
+
+    fn surface_detail(&self, impact: Impact) -> Impact {
+        let normal = (&impact.point - &self.center) / self.radius;
+        let (dpdu, dpdv) = self.tangents(&normal);
+        impact.with_uv(Self::uv(&normal)).with_tangents(dpdu, dpdv)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(&(&self.center - &extent), &(&self.center + &extent))
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let r = Ray::new(origin.clone(), direction.clone());
+        if self
+            .root(&r, &Interval::new(0.001, f32::INFINITY))
+            .is_none()
+        {
+            return 0.0;
+        }
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    /// Samples the cone of directions the sphere fills, uniformly by solid angle. From
+    /// inside the sphere every direction is equally likely.
+    fn random_towards(&self, origin: &Vec3, sampler: &mut Sampler) -> Option<Vec3> {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return Some(Vec3::random_unit(sampler));
+        };
+        let phi = 2.0 * PI * sampler.get_float();
+        let z = 1.0 + sampler.get_float() * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let uvw = Onb::from_w(&(&self.center - origin));
+        Some(uvw.local(&Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z)))
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        if self.material.is_emissive() {
+            lights.push(Box::new(Sphere {
+                center: self.center.clone(),
+                radius: self.radius,
+                material: ShapeOnly,
+            }));
+        }
     }
 }
 
+/// A list is a union of its objects, and as a light it picks one of them at random.
 impl Hittable for Vec<Box<dyn Hittable>> {
-    fn hit(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord> {
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
         let mut closest_so_far = interval.max;
         let mut record: Option<HitRecord> = None;
         for hittable in self.iter() {
-            if let Some(hr) = hittable.hit(r, &Interval::new(interval.min, closest_so_far)) {
+            let search = Interval::new(interval.min, closest_so_far);
+            if let Some(hr) = hittable.intersect(r, &search) {
                 if hr.impact.t < closest_so_far {
                     closest_so_far = hr.impact.t;
                     record = Some(hr);
@@ -94,4 +307,129 @@ impl Hittable for Vec<Box<dyn Hittable>> {
         }
         record
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(Aabb::EMPTY, |bbox, hittable| {
+            bbox.union(&hittable.bounding_box())
+        })
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = self
+            .iter()
+            .map(|hittable| hittable.pdf_value(origin, direction))
+            .sum();
+        total / self.len() as f32
+    }
+
+    fn random_towards(&self, origin: &Vec3, sampler: &mut Sampler) -> Option<Vec3> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = (sampler.get_float() * self.len() as f32) as usize;
+        self[index.min(self.len() - 1)].random_towards(origin, sampler)
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        for hittable in self.iter() {
+            hittable.collect_lights(lights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Hittable, Impact, Sphere};
+    use crate::interval::Interval;
+    use crate::material::{BumpMapped, Lambertian, Material, Scattering};
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::texture::ConstantColor;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn sphere_tangents_follow_uv() {
+        let sphere = Sphere {
+            center: Vec3::new(1.0, 2.0, 3.0),
+            radius: 2.0,
+            material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        };
+        let interval = Interval::new(0.001, f32::INFINITY);
+        let hit_towards = |point: &Vec3| {
+            let origin = &sphere.center + &(point - &sphere.center).unit_vector() * 10.0;
+            let direction = point - &origin;
+            let r = Ray::new(origin, direction);
+            sphere.hit(&r, &interval).unwrap().impact
+        };
+        let impact = hit_towards(&(&sphere.center + &Vec3::new(1.0, 0.5, -1.0)));
+        let step = 1e-3;
+        for (tangent, axis) in [(&impact.dpdu, 0), (&impact.dpdv, 1)] {
+            assert!(tangent.dot(&impact.normal).abs() < 1e-4);
+            let moved = hit_towards(&(&impact.point + &(tangent * step)));
+            let (du, dv) = (moved.uv.0 - impact.uv.0, moved.uv.1 - impact.uv.1);
+            let (along, across) = if axis == 0 { (du, dv) } else { (dv, du) };
+            assert!((along - step).abs() < 1e-4, "{along}");
+            assert!(across.abs() < 1e-4, "{across}");
+        }
+        assert!(impact.frame.u.dot(&impact.dpdu.unit_vector()) > 0.9999);
+
+        // Candidate hits are left without surface detail until one is chosen.
+        let r = Ray::new(Vec3::new(1.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let candidate = sphere.intersect(&r, &interval).unwrap().impact;
+        assert!(candidate.dpdu.is_near_zero());
+    }
+
+    #[test]
+    fn closest_hit_is_shaded_by_its_material() {
+        // A normal map that turns the shading normal to point along dpdu.
+        let sphere = Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: BumpMapped::normal_map(
+                Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+                ConstantColor::new(Vec3::new(1.0, 0.5, 0.5)),
+            ),
+        };
+        let r = Ray::new(Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let interval = Interval::new(0.001, f32::INFINITY);
+        let impact = sphere.hit(&r, &interval).unwrap().impact;
+        assert!(impact.normal.dot(&impact.dpdu.unit_vector()) > 0.9999);
+        assert_eq!(impact.geometric_normal.z, 1.0);
+        let candidate = sphere.intersect(&r, &interval).unwrap().impact;
+        assert_eq!(candidate.normal.z, 1.0);
+    }
+
+    #[test]
+    fn emissive_spheres_become_lights_without_copying_their_material() {
+        // An emitter that can't be cloned.
+        struct Glow;
+        impl Material for Glow {
+            fn sample(&self, _r: &Ray, _impact: &Impact, _: &mut Sampler) -> Option<Scattering> {
+                None
+            }
+
+            fn is_emissive(&self) -> bool {
+                true
+            }
+        }
+
+        let sphere = Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Glow,
+        };
+        let mut lights = Vec::new();
+        sphere.collect_lights(&mut lights);
+        assert_eq!(lights.len(), 1);
+        let origin = Vec3::new(0.0, 0.0, 10.0);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(
+            lights.pdf_value(&origin, &direction),
+            sphere.pdf_value(&origin, &direction)
+        );
+    }
 }
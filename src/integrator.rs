@@ -0,0 +1,314 @@
+//! Light-transport algorithms: how the camera estimates the light arriving along each ray.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+
+/// Estimates the radiance arriving along a camera ray. The camera averages one estimate
+/// per pixel sample.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut Sampler) -> Vec3;
+}
+
+/// The integrators that can be chosen by name, from the command line or a scene file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorMode {
+    /// Path tracing that only follows directions sampled from the BSDF.
+    Path,
+    /// Path tracing with next-event estimation.
+    Nee,
+    /// Path tracing with multiple importance sampling.
+    #[default]
+    Mis,
+    /// Shading normals, mapped from `[-1, 1]` to `[0, 1]`.
+    Normals,
+    /// The reflectance of the first surface hit.
+    Albedo,
+    /// Ambient occlusion.
+    Ao,
+}
+
+impl FromStr for IntegratorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorMode::Path),
+            "nee" => Ok(IntegratorMode::Nee),
+            "mis" => Ok(IntegratorMode::Mis),
+            "normals" => Ok(IntegratorMode::Normals),
+            "albedo" => Ok(IntegratorMode::Albedo),
+            "ao" => Ok(IntegratorMode::Ao),
+            _ => Err(format!(
+                "unknown integrator '{s}', expected one of path, nee, mis, normals, albedo or ao"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for IntegratorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IntegratorMode::Path => "path",
+            IntegratorMode::Nee => "nee",
+            IntegratorMode::Mis => "mis",
+            IntegratorMode::Normals => "normals",
+            IntegratorMode::Albedo => "albedo",
+            IntegratorMode::Ao => "ao",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How a [`PathIntegrator`] finds emitters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Only follow directions sampled from the BSDF, and count emitters when a path
+    /// happens to hit them.
+    None,
+    /// Also sample the scene's lights at every non-specular bounce, counting emitters hit
+    /// by BSDF sampling only when light sampling couldn't have found them.
+    Nee,
+    /// Sample both the BSDF and the lights, and weight the two with the power heuristic.
+    Mis,
+}
+
+impl LightSampling {
+    /// The weight of light found by sampling the lights, given the densities with which
+    /// the light and the BSDF pick its direction.
+    pub fn light_weight(self, light_pdf: f32, bsdf_pdf: f32) -> f32 {
+        match self {
+            LightSampling::None => 0.0,
+            LightSampling::Nee => 1.0,
+            LightSampling::Mis => power_heuristic(light_pdf, bsdf_pdf),
+        }
+    }
+
+    /// The weight of an emitter hit by a direction sampled from a non-specular BSDF.
+    pub fn bsdf_weight(self, bsdf_pdf: f32, light_pdf: f32) -> f32 {
+        match self {
+            LightSampling::None => 1.0,
+            LightSampling::Nee if light_pdf > 0.0 => 0.0,
+            LightSampling::Nee => 1.0,
+            LightSampling::Mis => power_heuristic(bsdf_pdf, light_pdf),
+        }
+    }
+}
+
+/// Veach's power heuristic with an exponent of two, for one sample from each technique.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Rays start a little way off surfaces so they don't hit the surface they left.
+fn hit_interval() -> Interval<f32> {
+    Interval::new(0.001, f32::INFINITY)
+}
+
+/// A unidirectional path tracer.
+///
+/// Unless `light_sampling` is [`LightSampling::None`], the scene's lights are sampled with
+/// a shadow ray at each bounce that isn't specular, and emitters the path then hits by
+/// chance are weighted so that no light is counted twice.
+///
+/// After `roulette_depth` bounces, paths are ended at random with a probability that grows
+/// as their throughput falls, and the survivors are weighted up to compensate. This
+/// keeps the estimate unbiased; `max_ray_depth` is only a safety cap.
+pub struct PathIntegrator {
+    pub light_sampling: LightSampling,
+    pub max_ray_depth: u32,
+    pub roulette_depth: u32,
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut Sampler) -> Vec3 {
+        let hit_interval = hit_interval();
+        let samples_lights = self.light_sampling != LightSampling::None;
+        let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+        // The BSDF density of the last bounce, if it sampled the lights as well.
+        let mut bsdf_pdf: Option<f32> = None;
+        for depth in 0..self.max_ray_depth {
+            let Some(hr) = scene.world.hit(&ray, &hit_interval) else {
+                radiance += &throughput * &scene.environment.radiance(&ray.direction);
+                break;
+            };
+            let weight = bsdf_pdf.map_or(1.0, |bsdf_pdf| {
+                let light_pdf = scene.lights.pdf_value(&ray.origin, &ray.direction);
+                self.light_sampling.bsdf_weight(bsdf_pdf, light_pdf)
+            });
+            radiance += &throughput * &hr.material.emitted(&ray, &hr.impact) * weight;
+            let Some(scattering) = hr.material.sample(&ray, &hr.impact, sampler) else {
+                break;
+            };
+            bsdf_pdf = None;
+            if samples_lights && !scattering.is_specular && !scene.lights.is_empty() {
+                radiance += &throughput * &self.sample_lights(&ray, &hr, scene, sampler);
+                bsdf_pdf = Some(scattering.pdf);
+            }
+            throughput *= scattering.attenuation;
+            ray = scattering.scattered;
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if sampler.get_float() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+        radiance
+    }
+}
+
+impl PathIntegrator {
+    /// Light arriving at a hit directly from one of the scene's lights, picked at random.
+    fn sample_lights(&self, r: &Ray, hr: &HitRecord, scene: &Scene, sampler: &mut Sampler) -> Vec3 {
+        let origin = &hr.impact.point;
+        let Some(direction) = scene.lights.random_towards(origin, sampler) else {
+            return Vec3::new(0.0, 0.0, 0.0);
+        };
+        let light_pdf = scene.lights.pdf_value(origin, &direction);
+        let bsdf = hr.material.eval(r, &hr.impact, &direction);
+        if light_pdf <= 0.0 || bsdf.is_near_zero() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let bsdf_pdf = hr.material.pdf(r, &hr.impact, &direction);
+        let weight = self.light_sampling.light_weight(light_pdf, bsdf_pdf);
+        // Only an emitter at the end of the shadow ray needs its surface detail, to look up
+        // what it gives off; anything else in the way just blocks the light.
+        let shadow_ray = Ray::new(origin.clone(), direction);
+        match scene.world.intersect(&shadow_ray, &hit_interval()) {
+            Some(light) if light.material.is_emissive() => {
+                let light = light.shaded();
+                bsdf * light.material.emitted(&shadow_ray, &light.impact) * (weight / light_pdf)
+            }
+            _ => Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// What a [`DebugIntegrator`] shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugChannel {
+    Normals,
+    Albedo,
+}
+
+/// Shows a property of the first surface each ray hits, and black for misses.
+pub struct DebugIntegrator {
+    pub channel: DebugChannel,
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut Sampler) -> Vec3 {
+        let Some(hr) = scene.world.hit(r, &hit_interval()) else {
+            return Vec3::new(0.0, 0.0, 0.0);
+        };
+        match self.channel {
+            DebugChannel::Normals => (&hr.impact.normal + &Vec3::new(1.0, 1.0, 1.0)) * 0.5,
+            // Emitters don't reflect, so show what they give off instead.
+            DebugChannel::Albedo => match hr.material.sample(r, &hr.impact, sampler) {
+                Some(scattering) => scattering.attenuation,
+                None => hr.material.emitted(r, &hr.impact),
+            },
+        }
+    }
+}
+
+/// The fraction of the hemisphere above the first hit that is open, estimated with one
+/// cosine-weighted ray per sample. Occluders further away than `distance` don't count; by
+/// default that is a tenth of the size of the world.
+pub struct AmbientOcclusion {
+    pub distance: Option<f32>,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut Sampler) -> Vec3 {
+        let Some(hr) = scene.world.hit(r, &hit_interval()) else {
+            return Vec3::new(1.0, 1.0, 1.0);
+        };
+        let uvw = Onb::from_w(&hr.impact.normal);
+        let direction = uvw.local(&Vec3::random_cosine_direction(sampler));
+        let probe = Ray::new(hr.impact.point.clone(), direction);
+        let bounds = &scene.bounds;
+        let distance = self.distance.unwrap_or_else(|| {
+            0.1 * Vec3::new(bounds.x.size(), bounds.y.size(), bounds.z.size()).length()
+        });
+        // An empty or unbounded world has no size to take a tenth of, so then nothing is
+        // too far away to occlude.
+        let reach = Interval::new(
+            0.001,
+            if distance.is_finite() {
+                distance
+            } else {
+                f32::INFINITY
+            },
+        );
+        if scene.world.intersect(&probe, &reach).is_some() {
+            Vec3::new(0.0, 0.0, 0.0)
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{AmbientOcclusion, DebugChannel, DebugIntegrator, Integrator};
+    use crate::hittable::Sphere;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::scene::Scene;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn debug_integrators_show_the_first_hit() {
+        let scene = Scene::new(Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Lambertian::new(Vec3::new(0.2, 0.4, 0.6)),
+        });
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut sampler = Sampler::new(1);
+
+        let normals = DebugIntegrator {
+            channel: DebugChannel::Normals,
+        };
+        let normal = normals.radiance(&r, &scene, &mut sampler);
+        assert_eq!((normal.x, normal.y, normal.z), (0.5, 0.5, 1.0));
+
+        let albedo = DebugIntegrator {
+            channel: DebugChannel::Albedo,
+        };
+        let albedo = albedo.radiance(&r, &scene, &mut sampler);
+        assert_eq!((albedo.x, albedo.y, albedo.z), (0.2, 0.4, 0.6));
+
+        // Nothing but the sphere itself, which is convex, so every probe escapes.
+        for distance in [None, Some(100.0)] {
+            let ao = AmbientOcclusion { distance };
+            for _ in 0..64 {
+                assert_eq!(ao.radiance(&r, &scene, &mut sampler).x, 1.0);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub struct Interval<T> {
     pub min: T,
     pub max: T,
@@ -27,4 +28,50 @@ impl<T> Interval<T> {
             *value
         }
     }
+
+    pub fn hull(&self, other: &Self) -> Self
+    where
+        T: PartialOrd + Copy,
+    {
+        Interval {
+            min: if other.min < self.min {
+                other.min
+            } else {
+                self.min
+            },
+            max: if other.max > self.max {
+                other.max
+            } else {
+                self.max
+            },
+        }
+    }
+}
+
+impl Interval<f32> {
+    pub const EMPTY: Interval<f32> = Interval {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+    };
+
+    pub fn size(&self) -> f32 {
+        self.max - self.min
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max < self.min
+    }
+
+    pub fn contains(&self, value: f32) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
 }
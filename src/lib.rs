@@ -0,0 +1,59 @@
+//! # Raytracing in Rust
+//!
+//! This project is an implementation of the raytracing in one weekend, written in rust.
+//!
+//! The renderer is a library: build a world out of [`Hittable`]s, configure a [`Camera`]
+//! with a [`CameraBuilder`] and call [`Camera::render`]. Scenes can also be loaded from
+//! TOML files with [`SceneFile`], or taken from the [`scenes`] built into the crate.
+
+pub(crate) mod aabb;
+pub(crate) mod bvh;
+pub(crate) mod camera;
+pub(crate) mod environment;
+pub(crate) mod film;
+pub(crate) mod hittable;
+pub(crate) mod integrator;
+pub(crate) mod interval;
+pub(crate) mod material;
+pub(crate) mod microfacet;
+pub(crate) mod noise;
+pub(crate) mod numeric_utilities;
+pub(crate) mod obj;
+pub(crate) mod onb;
+pub(crate) mod ray;
+pub(crate) mod sampler;
+pub(crate) mod scene;
+pub(crate) mod scene_file;
+pub mod scenes;
+pub(crate) mod texture;
+pub(crate) mod tonemap;
+pub(crate) mod triangle;
+pub(crate) mod vec3;
+
+pub use aabb::Aabb;
+pub use bvh::Bvh;
+pub use camera::{Camera, CameraBuilder, CameraError, RenderError, MAX_IMAGE_SIZE};
+pub use environment::{Environment, EnvironmentMap, Gradient, SolidColor};
+pub use film::Film;
+pub use hittable::{HitRecord, Hittable, Impact, Sphere};
+pub use integrator::{
+    AmbientOcclusion, DebugChannel, DebugIntegrator, Integrator, IntegratorMode, LightSampling,
+    PathIntegrator,
+};
+pub use interval::Interval;
+pub use material::{
+    BumpMapped, Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal, Scattering,
+};
+pub use obj::{load_obj, load_obj_with_material, ObjError, ObjMeshes, ObjWarning};
+pub use onb::Onb;
+pub use ray::Ray;
+pub use sampler::Sampler;
+pub use scene::Scene;
+pub use scene_file::{load_scene, SceneError, SceneFile};
+pub use texture::{
+    ConstantColor, ImageTexture, NoisePattern, NoiseTexture, ScalarTexture, SpatialChecker,
+    Texture, UvChecker, WrapMode,
+};
+pub use tonemap::{ToneMapOperator, ToneMapping};
+pub use triangle::{MeshVertices, Triangle, TriangleMesh};
+pub use vec3::Vec3;
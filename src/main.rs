@@ -1,100 +1,195 @@
-//#![warn(missing_docs)]
-
-//! # Raytracing in Rust
-//!
-//! This project is an implementation of the raytracing in one weekend, written in rust.
-
-use image::RgbImage;
-use std::error::Error;
-
-mod camera;
-mod hittable;
-mod interval;
-mod material;
-mod numeric_utilities;
-mod ray;
-mod vec3;
-
-use camera::CameraBuilder;
-use hittable::{Hittable, Sphere};
-use interval::Interval;
-use material::{Dielectric, Lambertian, Metal};
-use vec3::Vec3;
-
-use rand::distributions::Standard;
-use rand::prelude::*;
-
-fn get_rand() -> f32 {
-    rand::thread_rng().sample(Standard)
+//! Command-line front end for the `rust_tracing` renderer.
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rust_tracing::scenes;
+use rust_tracing::{
+    CameraBuilder, IntegratorMode, Scene, SceneFile, ToneMapOperator, ToneMapping, MAX_IMAGE_SIZE,
+};
+
+const EXIT_LOAD_FAILURE: u8 = 1;
+const EXIT_RENDER_FAILURE: u8 = 3;
+
+/// Renders a scene file, or one of the built-in scenes, to an image.
+#[derive(Parser)]
+#[command(
+    after_help = "Exits with 1 if the scene can't be loaded, 2 if the arguments are \
+                  invalid and 3 if the image can't be rendered or saved."
+)]
+struct Args {
+    /// A TOML scene file, or the name of a built-in scene.
+    #[arg(default_value = "spheres")]
+    scene: String,
+
+    /// Where to write the image; the format follows the extension. `.exr` and `.hdr`
+    /// keep the full dynamic range, other formats are quantised to 8 bits.
+    #[arg(short, long, default_value = "test2.jpg")]
+    output: PathBuf,
+
+    /// Image width in pixels.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=MAX_IMAGE_SIZE as i64))]
+    width: Option<u32>,
+
+    /// Image aspect ratio, width over height.
+    #[arg(long, value_parser = positive)]
+    aspect: Option<f32>,
+
+    /// Samples per pixel.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+
+    /// Maximum number of bounces per path.
+    #[arg(long)]
+    max_depth: Option<u32>,
+
+    /// Number of bounces before paths may be ended by Russian roulette.
+    #[arg(long)]
+    roulette_depth: Option<u32>,
+
+    /// Seed for every random draw; the same seed always gives the same image.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of render threads. Defaults to one per core.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// How to find light: path, nee (next-event estimation) or mis (multiple importance
+    /// sampling). The normals, albedo and ao (ambient occlusion) integrators are for
+    /// debugging.
+    #[arg(long)]
+    integrator: Option<IntegratorMode>,
+
+    /// Tone mapper for 8-bit output: clamp, reinhard, extended-reinhard, aces, hable or
+    /// agx.
+    #[arg(long)]
+    tonemap: Option<ToneMapOperator>,
+
+    /// Exposure adjustment in stops, applied before tone mapping.
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f32>,
+
+    /// Radiance that maps to white with the extended-reinhard tone mapper.
+    #[arg(long)]
+    white_point: Option<f32>,
+
+    /// Don't report progress while rendering.
+    #[arg(short, long)]
+    quiet: bool,
 }
 
-fn make_image() -> RgbImage {
-    let cam = CameraBuilder::new(400, 16.0 / 9.0)
-        .look_from(Vec3::new(13.0, 2.0, 3.0))
-        .build();
-
-    let mut world: Vec<Box<dyn Hittable>> = vec![
-        Box::new(Sphere {
-            // ground
-            center: Vec3::new(0.0, -1000.0, 0.0),
-            radius: 1000.0,
-            material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
-        }),
-        Box::new(Sphere {
-            center: Vec3::new(0.0, 1.0, 0.0),
-            radius: 1.0,
-            material: Dielectric::new(1.5),
-        }),
-        Box::new(Sphere {
-            center: Vec3::new(-4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: Lambertian::new(Vec3::new(0.4, 0.2, 0.1)),
-        }),
-        Box::new(Sphere {
-            center: Vec3::new(4.0, 1.0, 0.0),
-            radius: 1.0,
-            material: Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0),
-        }),
-    ];
-    for a in -11..11 {
-        for b in -11..11 {
-            let center = Vec3::new(
-                a as f32 + 0.9 * get_rand(),
-                0.2,
-                b as f32 + 0.9 * get_rand(),
-            );
-
-            if (&center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let choose_mat = get_rand();
-                if choose_mat < 0.8 {
-                    let albedo = Vec3::random() * Vec3::random();
-                    world.push(Box::new(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: Lambertian::new(albedo),
-                    }));
-                } else if choose_mat < 0.95 {
-                    let albedo = Vec3::random_in_range(0.5, 1.0);
-                    let fuzz = get_rand();
-                    world.push(Box::new(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: Metal::new(albedo, fuzz),
-                    }));
-                } else {
-                    world.push(Box::new(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: Dielectric::new(1.5),
-                    }));
-                }
-            }
-        }
+type LoadedScene = (CameraBuilder, Scene, ToneMapping);
+
+fn positive(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        Ok(_) => Err("expected a number above zero".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn load(args: &Args) -> Result<LoadedScene, Box<dyn std::error::Error>> {
+    if let Some((cam, scene)) = scenes::builtin(&args.scene, args.seed.unwrap_or(0)) {
+        return Ok((cam, scene, ToneMapping::default()));
     }
-    cam.render(&world)
+    let scene = SceneFile::load(&args.scene)?;
+    let (world, warnings) = scene.build_scene()?;
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    Ok((scene.camera_builder(), world, scene.tone_mapping()))
+}
+
+/// Parses the arguments, with the built-in scenes listed in the help for the scene.
+fn parse_args() -> Args {
+    let names: Vec<_> = scenes::BUILTIN_SCENES
+        .iter()
+        .map(|(name, _)| *name)
+        .collect();
+    let help = format!(
+        "A TOML scene file, or the name of a built-in scene ({})",
+        names.join(" or ")
+    );
+    let matches = Args::command()
+        .mut_arg("scene", |arg| arg.help(help))
+        .get_matches();
+    Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    make_image().save("test2.jpg")?;
-    Ok(())
+fn main() -> ExitCode {
+    let args = parse_args();
+
+    let (mut cam, scene, mut tone_mapping) = match load(&args) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_LOAD_FAILURE);
+        }
+    };
+    if let Some(width) = args.width {
+        cam = cam.image_width(width);
+    }
+    if let Some(aspect) = args.aspect {
+        cam = cam.aspect_ratio(aspect);
+    }
+    if let Some(spp) = args.spp {
+        cam = cam.pixel_samples(spp);
+    }
+    if let Some(max_depth) = args.max_depth {
+        cam = cam.max_ray_depth(max_depth);
+    }
+    if let Some(roulette_depth) = args.roulette_depth {
+        cam = cam.roulette_depth(roulette_depth);
+    }
+    if let Some(seed) = args.seed {
+        cam = cam.seed(seed);
+    }
+    if let Some(threads) = args.threads {
+        cam = cam.threads(threads);
+    }
+    if let Some(integrator) = args.integrator {
+        cam = cam.integrator(integrator);
+    }
+    if let Some(operator) = args.tonemap {
+        tone_mapping.operator = operator;
+    }
+    if let Some(exposure) = args.exposure {
+        tone_mapping.exposure = exposure;
+    }
+    if let Some(white_point) = args.white_point {
+        tone_mapping.white_point = white_point;
+    }
+
+    let cam = match cam.build() {
+        Ok(cam) => cam,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_RENDER_FAILURE);
+        }
+    };
+    let rendered = cam.render_with_progress(&scene, |finished, total| {
+        if !args.quiet {
+            eprint!("\rRendering: {}/{} tiles", finished, total);
+            if finished == total {
+                eprintln!();
+            }
+            let _ = std::io::stderr().flush();
+        }
+    });
+    let film = match rendered {
+        Ok(film) => film,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_RENDER_FAILURE);
+        }
+    };
+
+    if let Err(error) = film.save(&args.output, &tone_mapping) {
+        eprintln!("error: {}: {}", args.output.display(), error);
+        return ExitCode::from(EXIT_RENDER_FAILURE);
+    }
+    ExitCode::SUCCESS
 }
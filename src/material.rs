@@ -1,66 +1,396 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
 use crate::hittable::Impact;
-use crate::numeric_utilities;
+use crate::microfacet::{self, TrowbridgeReitz};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{ConstantColor, ScalarTexture, Texture};
 use crate::vec3::Vec3;
 
+/// A direction sampled from a material, and the weight light arriving along it carries.
 pub struct Scattering {
     pub scattered: Ray,
+    /// The BSDF times the cosine over the PDF, which for most materials is their albedo.
     pub attenuation: Vec3,
+    /// The density with which the direction was picked, per unit solid angle. Zero for
+    /// specular bounces, which have no density.
+    pub pdf: f32,
+    /// Whether the scattered direction was the only one possible, as for a mirror or
+    /// glass. Sampling lights is pointless after a specular bounce.
+    pub is_specular: bool,
 }
 
-pub trait Material {
-    fn scatter(&self, r: &Ray, impact: &Impact) -> Option<Scattering>;
+pub trait Material: Send + Sync {
+    /// Picks a direction to continue the path in, or returns `None` if the light is
+    /// absorbed.
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering>;
+
+    /// The density, per unit solid angle, with which [`Material::sample`] picks
+    /// `direction`. Zero for specular materials and by default.
+    fn pdf(&self, _r: &Ray, _impact: &Impact, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    /// The BSDF times the cosine of the angle to the normal, for light arriving along
+    /// `direction` and leaving back towards the origin of `r`. Purely specular materials
+    /// can't be evaluated and return black, which is the default.
+    fn eval(&self, _r: &Ray, _impact: &Impact, _direction: &Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Radiance given off by the surface towards the origin of `r`. Most materials don't
+    /// glow, so this defaults to black.
+    fn emitted(&self, _r: &Ray, _impact: &Impact) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Whether [`Material::emitted`] can be anything but black, so that shapes made of the
+    /// material are worth sampling as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Adjusts the shading of a hit before the material is used there, as bump mapping
+    /// does. [`Hittable::hit`] calls this once for the closest hit, so the other methods
+    /// see the adjusted hit. Leaves it as it is by default.
+    ///
+    /// [`Hittable::hit`]: crate::hittable::Hittable::hit
+    fn shade(&self, impact: Impact) -> Impact {
+        impact
+    }
 }
 
+/// A shared handle to a material. Primitives holding clones of one `Arc` all use the same
+/// instance, so a material needn't be copied into every object that uses it, and objects
+/// with different materials can be built from `Arc<dyn Material>`.
+impl<M> Material for Arc<M>
+where
+    M: Material + ?Sized,
+{
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
+        (**self).sample(r, impact, sampler)
+    }
+
+    fn pdf(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> f32 {
+        (**self).pdf(r, impact, direction)
+    }
+
+    fn eval(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> Vec3 {
+        (**self).eval(r, impact, direction)
+    }
+
+    fn emitted(&self, r: &Ray, impact: &Impact) -> Vec3 {
+        (**self).emitted(r, impact)
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn shade(&self, impact: Impact) -> Impact {
+        (**self).shade(impact)
+    }
+}
+
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Vec3) -> Self {
-        Lambertian { albedo }
+        Lambertian::textured(ConstantColor::new(albedo))
+    }
+
+    /// A Lambertian surface whose albedo varies over it.
+    pub fn textured(albedo: impl Texture + 'static) -> Self {
+        Lambertian {
+            albedo: Arc::new(albedo),
+        }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r: &Ray, impact: &Impact) -> Option<Scattering> {
-        let mut direction = &impact.normal + &Vec3::random_unit();
-        if direction.is_near_zero() {
-            direction = impact.normal.clone();
+    /// Cosine-weighted sampling, which cancels the cosine in the rendering equation so
+    /// the weight is just the albedo.
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
+        let direction = impact.frame.local(&Vec3::random_cosine_direction(sampler));
+        let pdf = self.pdf(r, impact, &direction);
+        if pdf <= 0.0 {
+            return None;
         }
         Some(Scattering {
             scattered: Ray::new(impact.point.clone(), direction),
-            attenuation: self.albedo.clone(),
+            attenuation: self.albedo.value(impact.uv, &impact.point),
+            pdf,
+            is_specular: false,
         })
     }
+
+    fn pdf(&self, _r: &Ray, impact: &Impact, direction: &Vec3) -> f32 {
+        let cosine = impact.normal.dot(&direction.unit_vector());
+        cosine.max(0.0) / PI
+    }
+
+    fn eval(&self, _r: &Ray, impact: &Impact, direction: &Vec3) -> Vec3 {
+        let cosine = impact.normal.dot(&direction.unit_vector());
+        self.albedo.value(impact.uv, &impact.point) * (cosine.max(0.0) / PI)
+    }
 }
 
+#[derive(Clone)]
 pub struct Metal {
-    albedo: Vec3,
-    fuzz: f32,
+    albedo: Arc<dyn Texture>,
+    fuzz: Arc<dyn ScalarTexture>,
 }
 
 impl Metal {
     pub fn new(albedo: Vec3, fuzz: f32) -> Self {
-        Metal { albedo, fuzz }
+        Metal::textured(ConstantColor::new(albedo), fuzz)
+    }
+
+    /// A metal whose albedo varies over its surface.
+    pub fn textured(albedo: impl Texture + 'static, fuzz: f32) -> Self {
+        Metal {
+            albedo: Arc::new(albedo),
+            fuzz: Arc::new(fuzz),
+        }
+    }
+
+    /// A fuzz that varies over the surface, in place of the constant one.
+    pub fn fuzz_texture(mut self, fuzz: impl ScalarTexture + 'static) -> Self {
+        self.fuzz = Arc::new(fuzz);
+        self
+    }
+
+    fn fuzz(&self, impact: &Impact) -> f32 {
+        self.fuzz.scalar(impact.uv, &impact.point)
+    }
+
+    /// The density of the directions `sample` picks. The fuzzed reflection is the
+    /// mirror direction plus a point on a sphere of radius `fuzz`, so this is the
+    /// projection of that sphere onto the sphere of directions: for each place the
+    /// direction crosses it, the squared distance over the cosine to the sphere's normal.
+    fn fuzz_pdf(fuzz: f32, reflected: &Vec3, direction: &Vec3) -> f32 {
+        let b = direction.dot(reflected);
+        let discriminant = b * b - 1.0 + fuzz * fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let sqrt_d = discriminant.sqrt();
+        let density: f32 = [b - sqrt_d, b + sqrt_d]
+            .iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t)
+            .sum();
+        density / (4.0 * PI * fuzz * sqrt_d)
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r: &Ray, impact: &Impact) -> Option<Scattering> {
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
+        let fuzz = self.fuzz(impact);
         let mut reflected = r.direction.unit_vector().reflect(&impact.normal);
-        reflected += Vec3::random_unit() * self.fuzz;
+        reflected += Vec3::random_unit(sampler) * fuzz;
         if reflected.dot(&impact.normal) > 0.0 {
+            let pdf = self.pdf(r, impact, &reflected);
             Some(Scattering {
                 scattered: Ray::new(impact.point.clone(), reflected),
-                attenuation: self.albedo.clone(),
+                attenuation: self.albedo.value(impact.uv, &impact.point),
+                pdf,
+                is_specular: fuzz <= 0.0,
             })
         } else {
             None
         }
     }
+
+    fn pdf(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> f32 {
+        let fuzz = self.fuzz(impact);
+        let direction = direction.unit_vector();
+        if fuzz <= 0.0 || direction.dot(&impact.normal) <= 0.0 {
+            return 0.0;
+        }
+        let reflected = r.direction.unit_vector().reflect(&impact.normal);
+        Self::fuzz_pdf(fuzz, &reflected, &direction)
+    }
+
+    /// Every direction that isn't absorbed carries the albedo, so the BSDF is the albedo
+    /// weighted by how often the direction is picked.
+    fn eval(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> Vec3 {
+        self.albedo.value(impact.uv, &impact.point) * self.pdf(r, impact, direction)
+    }
 }
 
+/// A metal with rough reflection from a Trowbridge-Reitz (GGX) distribution of
+/// microfacets, and the Fresnel reflectance of its complex refractive index `eta + ik`,
+/// given per colour channel.
+///
+/// Directions are sampled from the microfacet normals visible from the incoming ray.
+/// Roughness can differ along `dpdu` and `dpdv` for brushed metals, and over the surface
+/// when given as a texture. A conductor with no roughness, the default, is a perfect
+/// mirror.
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Vec3,
+    k: Vec3,
+    roughness_u: Arc<dyn ScalarTexture>,
+    roughness_v: Arc<dyn ScalarTexture>,
+}
+
+impl Conductor {
+    pub fn new(eta: Vec3, k: Vec3) -> Self {
+        Conductor {
+            eta,
+            k,
+            roughness_u: Arc::new(0.0),
+            roughness_v: Arc::new(0.0),
+        }
+    }
+
+    pub fn gold() -> Self {
+        Conductor::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+        )
+    }
+
+    pub fn copper() -> Self {
+        Conductor::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+        )
+    }
+
+    pub fn aluminium() -> Self {
+        Conductor::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+        )
+    }
+
+    /// Roughness from zero, a mirror, to one, very rough.
+    pub fn roughness(self, roughness: f32) -> Self {
+        self.anisotropic_roughness(roughness, roughness)
+    }
+
+    /// Separate roughness along `dpdu` and along `dpdv`.
+    pub fn anisotropic_roughness(self, roughness_u: f32, roughness_v: f32) -> Self {
+        self.anisotropic_roughness_texture(roughness_u, roughness_v)
+    }
+
+    /// Roughness that varies over the surface.
+    pub fn roughness_texture(mut self, roughness: impl ScalarTexture + 'static) -> Self {
+        let roughness: Arc<dyn ScalarTexture> = Arc::new(roughness);
+        self.roughness_u = roughness.clone();
+        self.roughness_v = roughness;
+        self
+    }
+
+    /// Roughness along `dpdu` and along `dpdv` that varies over the surface.
+    pub fn anisotropic_roughness_texture(
+        mut self,
+        roughness_u: impl ScalarTexture + 'static,
+        roughness_v: impl ScalarTexture + 'static,
+    ) -> Self {
+        self.roughness_u = Arc::new(roughness_u);
+        self.roughness_v = Arc::new(roughness_v);
+        self
+    }
+
+    /// The microfacet distribution at the hit, from the roughness there.
+    fn distribution(&self, impact: &Impact) -> TrowbridgeReitz {
+        TrowbridgeReitz::from_roughness(
+            self.roughness_u.scalar(impact.uv, &impact.point),
+            self.roughness_v.scalar(impact.uv, &impact.point),
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f32) -> Vec3 {
+        Vec3::new(
+            microfacet::fresnel_conductor(cos_theta, self.eta.x, self.k.x),
+            microfacet::fresnel_conductor(cos_theta, self.eta.y, self.k.y),
+            microfacet::fresnel_conductor(cos_theta, self.eta.z, self.k.z),
+        )
+    }
+
+    /// The directions towards the origin of `r` and along `direction`, in the shading
+    /// frame.
+    fn local(r: &Ray, impact: &Impact, direction: &Vec3) -> (Vec3, Vec3) {
+        let wo = impact.frame.to_local(&(&r.direction * -1.0).unit_vector());
+        let wi = impact.frame.to_local(&direction.unit_vector());
+        (wo, wi)
+    }
+
+    /// The microfacet normal that reflects `wo` into `wi`, if both are above the surface.
+    fn half_vector(wo: &Vec3, wi: &Vec3) -> Option<Vec3> {
+        let wm = wo + wi;
+        (wo.z > 0.0 && wi.z > 0.0 && !wm.is_near_zero()).then(|| wm.unit_vector())
+    }
+}
+
+impl Material for Conductor {
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
+        let wo = impact.frame.to_local(&(&r.direction * -1.0).unit_vector());
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let distribution = self.distribution(impact);
+        if distribution.is_smooth() {
+            let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+            return Some(Scattering {
+                scattered: Ray::new(impact.point.clone(), impact.frame.local(&wi)),
+                attenuation: self.fresnel(wo.z),
+                pdf: 0.0,
+                is_specular: true,
+            });
+        }
+
+        let wm = distribution.sample_visible_normal(&wo, sampler.get_float(), sampler.get_float());
+        let wi = &wm * (2.0 * wo.dot(&wm)) - wo.clone();
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let direction = impact.frame.local(&wi);
+        let attenuation =
+            self.fresnel(wo.dot(&wm)) * (distribution.g(&wo, &wi) / distribution.g1(&wo));
+        Some(Scattering {
+            pdf: self.pdf(r, impact, &direction),
+            scattered: Ray::new(impact.point.clone(), direction),
+            attenuation,
+            is_specular: false,
+        })
+    }
+
+    fn pdf(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> f32 {
+        let distribution = self.distribution(impact);
+        if distribution.is_smooth() {
+            return 0.0;
+        }
+        let (wo, wi) = Self::local(r, impact, direction);
+        let Some(wm) = Self::half_vector(&wo, &wi) else {
+            return 0.0;
+        };
+        distribution.visible_normal_pdf(&wo, &wm) / (4.0 * wo.dot(&wm))
+    }
+
+    fn eval(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> Vec3 {
+        let distribution = self.distribution(impact);
+        if distribution.is_smooth() {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let (wo, wi) = Self::local(r, impact, direction);
+        let Some(wm) = Self::half_vector(&wo, &wi) else {
+            return Vec3::new(0.0, 0.0, 0.0);
+        };
+        let d = distribution.d(&wm);
+        let g = distribution.g(&wo, &wi);
+        self.fresnel(wo.dot(&wm)) * (d * g / (4.0 * wo.z))
+    }
+}
+
+#[derive(Clone)]
 pub struct Dielectric {
     refractive_index: f32,
 }
@@ -78,15 +408,14 @@ impl Dielectric {
         r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
     }
 
-    fn get_random_reflection(cos_theta: f32, refraction_ratio: f32) -> bool {
+    fn get_random_reflection(cos_theta: f32, refraction_ratio: f32, sampler: &mut Sampler) -> bool {
         let reflectance = Self::refelectance(cos_theta, refraction_ratio);
-        let rand = numeric_utilities::get_rand_float();
-        reflectance > rand
+        reflectance > sampler.get_float()
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r: &Ray, impact: &Impact) -> Option<Scattering> {
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
         let refraction_ratio = if impact.is_front_face {
             1.0 / self.refractive_index
         } else {
@@ -97,7 +426,8 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let should_reflect = cannot_refract
-            || (impact.is_front_face && Self::get_random_reflection(cos_theta, refraction_ratio));
+            || (impact.is_front_face
+                && Self::get_random_reflection(cos_theta, refraction_ratio, sampler));
         let direction = if should_reflect {
             r.direction.reflect(&impact.normal)
         } else {
@@ -109,6 +439,288 @@ impl Material for Dielectric {
         Some(Scattering {
             scattered: Ray::new(impact.point.clone(), direction),
             attenuation: Vec3::new(1.0, 1.0, 1.0),
+            pdf: 0.0,
+            is_specular: true,
         })
     }
 }
+
+/// A surface that emits light evenly from its front face and reflects nothing. Any
+/// hittable can be made into an area light by giving it this material.
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(&self, _r: &Ray, _impact: &Impact, _sampler: &mut Sampler) -> Option<Scattering> {
+        None
+    }
+
+    fn emitted(&self, _r: &Ray, impact: &Impact) -> Vec3 {
+        if impact.is_front_face {
+            self.emit.clone()
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        !self.emit.is_near_zero()
+    }
+}
+
+/// How far along `u` and `v` a height texture is sampled to find its slope.
+const BUMP_DELTA: f32 = 1e-3;
+
+#[derive(Clone)]
+enum Perturbation {
+    NormalMap(Arc<dyn Texture>),
+    Height {
+        texture: Arc<dyn Texture>,
+        strength: f32,
+    },
+}
+
+/// Wraps a material to perturb the shading normal it sees, from a tangent-space normal map
+/// or from a height texture. The geometric normal is untouched, and the perturbed normal
+/// is kept on its side of the surface.
+#[derive(Clone)]
+pub struct BumpMapped<M> {
+    material: M,
+    perturbation: Perturbation,
+}
+
+impl<M> BumpMapped<M> {
+    /// The red, green and blue channels of `map` hold the normal's components along
+    /// `dpdu`, along `dpdv` and along the normal, each mapped from `[-1, 1]` to `[0, 1]`.
+    /// Image normal maps should be loaded with [`ImageTexture::load_data`].
+    ///
+    /// [`ImageTexture::load_data`]: crate::texture::ImageTexture::load_data
+    pub fn normal_map(material: M, map: impl Texture + 'static) -> Self {
+        BumpMapped {
+            material,
+            perturbation: Perturbation::NormalMap(Arc::new(map)),
+        }
+    }
+
+    /// Shades as though the surface were displaced along its normal by `strength` times
+    /// the mean of the texture's channels.
+    pub fn height(material: M, height: impl Texture + 'static, strength: f32) -> Self {
+        BumpMapped {
+            material,
+            perturbation: Perturbation::Height {
+                texture: Arc::new(height),
+                strength,
+            },
+        }
+    }
+
+    fn perturb(&self, impact: Impact) -> Impact {
+        let normal = match &self.perturbation {
+            Perturbation::NormalMap(map) => {
+                let n = map.value(impact.uv, &impact.point) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
+                let frame = &impact.frame;
+                // The frame's v axis follows dpdv only up to sign.
+                let bitangent = if frame.v.dot(&impact.dpdv) < 0.0 {
+                    &frame.v * -1.0
+                } else {
+                    frame.v.clone()
+                };
+                &frame.u * n.x + bitangent * n.y + &frame.w * n.z
+            }
+            Perturbation::Height { texture, strength } => {
+                let height = |uv: (f32, f32), point: &Vec3| {
+                    let value = texture.value(uv, point);
+                    strength * (value.x + value.y + value.z) / 3.0
+                };
+                let (u, v) = impact.uv;
+                let base = height(impact.uv, &impact.point);
+                let along_u = height(
+                    (u + BUMP_DELTA, v),
+                    &(&impact.point + &(&impact.dpdu * BUMP_DELTA)),
+                );
+                let along_v = height(
+                    (u, v + BUMP_DELTA),
+                    &(&impact.point + &(&impact.dpdv * BUMP_DELTA)),
+                );
+                let dpdu = &impact.dpdu + &impact.normal * ((along_u - base) / BUMP_DELTA);
+                let dpdv = &impact.dpdv + &impact.normal * ((along_v - base) / BUMP_DELTA);
+                let bumped = dpdu.cross(&dpdv);
+                if bumped.dot(&impact.normal) < 0.0 {
+                    bumped * -1.0
+                } else {
+                    bumped
+                }
+            }
+        };
+        if normal.is_near_zero() {
+            return impact;
+        }
+        impact
+            .with_shading_normal(normal.unit_vector())
+            .with_frame()
+    }
+}
+
+impl<M> Material for BumpMapped<M>
+where
+    M: Material,
+{
+    fn sample(&self, r: &Ray, impact: &Impact, sampler: &mut Sampler) -> Option<Scattering> {
+        self.material.sample(r, impact, sampler)
+    }
+
+    fn pdf(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> f32 {
+        self.material.pdf(r, impact, direction)
+    }
+
+    fn eval(&self, r: &Ray, impact: &Impact, direction: &Vec3) -> Vec3 {
+        self.material.eval(r, impact, direction)
+    }
+
+    fn emitted(&self, r: &Ray, impact: &Impact) -> Vec3 {
+        self.material.emitted(r, impact)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// The normal is perturbed here, once per hit, rather than in each of the methods
+    /// above.
+    fn shade(&self, impact: Impact) -> Impact {
+        self.material.shade(self.perturb(impact))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{BumpMapped, Conductor, Lambertian, Material, Metal};
+    use crate::hittable::Impact;
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::texture::{ConstantColor, Texture};
+    use crate::vec3::Vec3;
+
+    /// A height that rises along x.
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, _uv: (f32, f32), point: &Vec3) -> Vec3 {
+            Vec3::new(point.x, point.x, point.x)
+        }
+    }
+
+    #[test]
+    fn rough_conductor_pdf_integrates_to_one() {
+        let r = Ray::new(Vec3::new(-1.0, 0.5, 0.2), Vec3::new(1.0, -0.5, -0.2));
+        let impact =
+            Impact::new(&r, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0).with_frame();
+        for conductor in [
+            Conductor::aluminium().anisotropic_roughness(0.3, 0.6),
+            Conductor::aluminium().anisotropic_roughness(0.0, 0.5),
+        ] {
+            let mut sampler = Sampler::new(4);
+            let n = 200_000;
+            let mut total = 0.0;
+            // Half the directions are uniform and half come from the conductor itself, so
+            // that the narrow lobe of a surface smooth along one axis is found.
+            let uniform = 1.0 / (4.0 * std::f32::consts::PI);
+            for _ in 0..n {
+                let direction = if sampler.get_float() < 0.5 {
+                    Vec3::random_unit(&mut sampler)
+                } else {
+                    match conductor.sample(&r, &impact, &mut sampler) {
+                        Some(scattering) => scattering.scattered.direction,
+                        None => continue,
+                    }
+                };
+                let pdf = conductor.pdf(&r, &impact, &direction);
+                total += pdf / (0.5 * uniform + 0.5 * pdf);
+            }
+            // A little density is lost to reflections that would go below the surface.
+            let estimate = total / n as f32;
+            assert!(estimate > 0.9 && estimate <= 1.01, "{estimate}");
+        }
+    }
+
+    #[test]
+    fn roughness_textures_vary_over_the_surface() {
+        let conductor = Conductor::aluminium().roughness_texture(Ramp);
+        let metal = Metal::new(Vec3::new(0.8, 0.8, 0.8), 0.0).fuzz_texture(Ramp);
+        let mut sampler = Sampler::new(3);
+        for (x, is_specular) in [(0.0, true), (0.5, false)] {
+            let r = Ray::new(Vec3::new(x - 1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+            let impact =
+                Impact::new(&r, Vec3::new(x, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0).with_frame();
+            for material in [&conductor as &dyn Material, &metal] {
+                let scattering = (0..16)
+                    .find_map(|_| material.sample(&r, &impact, &mut sampler))
+                    .unwrap();
+                assert_eq!(scattering.is_specular, is_specular);
+            }
+        }
+    }
+
+    #[test]
+    fn bump_mapping_tilts_only_the_shading_normal() {
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let impact = Impact::new(&r, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0)
+            .with_tangents(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0))
+            .with_frame();
+        let lambertian = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+
+        let flat = BumpMapped::normal_map(
+            lambertian.clone(),
+            ConstantColor::new(Vec3::new(0.5, 0.5, 1.0)),
+        );
+        assert!((&flat.shade(impact.clone()).normal - &impact.normal).length() < 1e-6);
+        let tilted = BumpMapped::normal_map(
+            lambertian.clone(),
+            ConstantColor::new(Vec3::new(1.0, 0.5, 0.5)),
+        );
+        assert!((tilted.shade(impact.clone()).normal.x - 1.0).abs() < 1e-6);
+
+        // A slope of one along x tilts the normal 45 degrees back towards -x.
+        let bumped = BumpMapped::height(lambertian, Ramp, 1.0).shade(impact.clone());
+        let expected = Vec3::new(-1.0, 1.0, 0.0).unit_vector();
+        assert!((&bumped.normal - &expected).length() < 1e-3);
+        assert_eq!(bumped.geometric_normal.y, 1.0);
+        assert!(bumped.is_front_face);
+    }
+
+    #[test]
+    fn sampled_weight_is_eval_over_pdf() {
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let impact =
+            Impact::new(&r, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0).with_frame();
+        let materials: [&dyn Material; 5] = [
+            &Lambertian::new(Vec3::new(0.8, 0.4, 0.2)),
+            &Metal::new(Vec3::new(0.8, 0.4, 0.2), 0.3),
+            &Conductor::gold().roughness(0.4),
+            &Conductor::copper().anisotropic_roughness(0.2, 0.7),
+            &Conductor::aluminium().anisotropic_roughness(0.0, 0.5),
+        ];
+        let mut sampler = Sampler::new(3);
+        for material in materials {
+            for _ in 0..100 {
+                let Some(scattering) = material.sample(&r, &impact, &mut sampler) else {
+                    continue;
+                };
+                let direction = &scattering.scattered.direction;
+                let pdf = material.pdf(&r, &impact, direction);
+                let weight = material.eval(&r, &impact, direction) / pdf;
+                assert!((pdf - scattering.pdf).abs() <= 1e-4 * pdf);
+                assert!((&weight - &scattering.attenuation).length() < 1e-4);
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The Trowbridge-Reitz (GGX) microfacet distribution and the Fresnel equations, for
+//! rough reflection.
+//!
+//! Directions are in a local shading frame with the normal along +z, as given by
+//! [`Onb::to_local`](crate::onb::Onb::to_local).
+
+use std::f32::consts::PI;
+
+use crate::vec3::Vec3;
+
+const MIN_ALPHA: f32 = 1e-4;
+
+/// The Trowbridge-Reitz distribution of microfacet normals, with Smith's masking
+/// function. `alpha_x` and `alpha_y` are the roughness along the frame's u and v axes.
+#[derive(Clone, Copy, Debug)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    /// Takes perceptual roughness, which is squared to give alpha so that it looks about
+    /// linear. Alpha is kept above a small minimum, as the distribution divides by it, so
+    /// a surface smooth along only one axis is very nearly a mirror along it.
+    pub fn from_roughness(roughness_u: f32, roughness_v: f32) -> Self {
+        TrowbridgeReitz {
+            alpha_x: (roughness_u * roughness_u).max(MIN_ALPHA),
+            alpha_y: (roughness_v * roughness_v).max(MIN_ALPHA),
+        }
+    }
+
+    /// Below this roughness the surface is treated as a perfect mirror.
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// The density of microfacet normals `wm`, per unit projected area.
+    pub fn d(&self, wm: &Vec3) -> f32 {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let e = (wm.x / self.alpha_x).powi(2) + (wm.y / self.alpha_y).powi(2) + wm.z * wm.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    /// Smith's auxiliary function, the ratio of hidden to visible microfacet area seen
+    /// from `w`.
+    fn lambda(&self, w: &Vec3) -> f32 {
+        if w.z == 0.0 {
+            return f32::INFINITY;
+        }
+        let alpha2_tan2 =
+            ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / (w.z * w.z);
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// The fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// The density of the normals [`TrowbridgeReitz::sample_visible_normal`] picks when
+    /// seen from `wo`.
+    pub fn visible_normal_pdf(&self, wo: &Vec3, wm: &Vec3) -> f32 {
+        self.g1(wo) * wo.dot(wm).max(0.0) * self.d(wm) / wo.z
+    }
+
+    /// Samples a microfacet normal in proportion to how much of it is visible from `wo`,
+    /// which must be above the surface (Heitz, 2018).
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: f32, u2: f32) -> Vec3 {
+        // Stretch the view so the distribution becomes a hemisphere of unit roughness.
+        let vh = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit_vector();
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // A point on the projected disk, squashed onto the part of it that is visible.
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        let nh = t1 * p1 + t2 * p2 + vh * p3;
+
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).unit_vector()
+    }
+}
+
+/// The Fresnel reflectance of a conductor with complex refractive index `eta + ik`, for
+/// light arriving from air at an angle with cosine `cos_theta`.
+pub fn fresnel_conductor(cos_theta: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let cos_theta = cos2.sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * a * cos_theta;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{fresnel_conductor, TrowbridgeReitz};
+    use crate::sampler::Sampler;
+    use crate::vec3::Vec3;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn projected_normal_density_integrates_to_one() {
+        // Every view sees one unit of projected microfacet area, so integrating D times
+        // the cosine of the normal over the hemisphere gives one.
+        let distribution = TrowbridgeReitz::from_roughness(0.5, 0.8);
+        let mut sampler = Sampler::new(2);
+        let n = 200_000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let wm = Vec3::random_unit(&mut sampler);
+            let wm = Vec3::new(wm.x, wm.y, wm.z.abs());
+            total += distribution.d(&wm) * wm.z * 2.0 * PI;
+        }
+        let estimate = total / n as f32;
+        assert!((estimate - 1.0).abs() < 0.02, "{estimate}");
+    }
+
+    #[test]
+    fn conductor_fresnel_rises_to_one_at_grazing() {
+        let (eta, k) = (0.2, 3.9);
+        let normal = fresnel_conductor(1.0, eta, k);
+        let expected = ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+        assert!((normal - expected).abs() < 1e-5);
+        assert!(fresnel_conductor(0.0, eta, k) > 0.999);
+    }
+}
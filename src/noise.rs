@@ -0,0 +1,148 @@
+//! Seeded gradient and cellular noise, for procedural textures.
+
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+const TABLE_SIZE: usize = 256;
+
+/// Lattice noise over 3D space: Perlin's gradient noise and Worley's cellular noise, both
+/// repeating every 256 units. Two `Noise`s made with the same seed are identical.
+pub struct Noise {
+    gradients: Vec<Vec3>,
+    /// The feature point of each Worley cell, as an offset within the cell.
+    features: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut sampler = Sampler::new(seed);
+        let gradients = (0..TABLE_SIZE)
+            .map(|_| Vec3::random_unit(&mut sampler))
+            .collect();
+        let features = (0..TABLE_SIZE)
+            .map(|_| Vec3::random(&mut sampler))
+            .collect();
+        let permutations = [(); 3].map(|_| Self::permutation(&mut sampler));
+        Noise {
+            gradients,
+            features,
+            permutations,
+        }
+    }
+
+    fn permutation(sampler: &mut Sampler) -> Vec<usize> {
+        let mut permutation: Vec<usize> = (0..TABLE_SIZE).collect();
+        for i in (1..TABLE_SIZE).rev() {
+            let j = ((sampler.get_float() * (i + 1) as f32) as usize).min(i);
+            permutation.swap(i, j);
+        }
+        permutation
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        let [x, y, z] = &self.permutations;
+        let wrap = |n: i64| (n & (TABLE_SIZE as i64 - 1)) as usize;
+        x[wrap(i)] ^ y[wrap(j)] ^ z[wrap(k)]
+    }
+
+    /// Perlin's gradient noise, smooth and roughly in `[-1, 1]`, and zero at every lattice
+    /// point.
+    pub fn perlin(&self, p: &Vec3) -> f32 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        // Perlin's quintic fade, so that the noise has continuous second derivatives.
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (su, sv, sw) = (fade(u), fade(v), fade(w));
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = &self.gradients[self.hash(i + di, j + dj, k + dk)];
+                    let offset = Vec3::new(u - di as f32, v - dj as f32, w - dk as f32);
+                    let weight = |s: f32, d: i64| if d == 0 { 1.0 - s } else { s };
+                    sum += weight(su, di) * weight(sv, dj) * weight(sw, dk) * gradient.dot(&offset);
+                }
+            }
+        }
+        sum
+    }
+
+    /// Worley's cellular noise: the distance to the nearest of one random point per unit
+    /// cell.
+    pub fn worley(&self, p: &Vec3) -> f32 {
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let mut nearest = f32::INFINITY;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                    let cell = Vec3::new(ci as f32, cj as f32, ck as f32);
+                    let feature = &cell + &self.features[self.hash(ci, cj, ck)];
+                    nearest = nearest.min((&feature - p).length());
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Fractal Brownian motion: `octaves` layers of Perlin noise, each at twice the
+    /// frequency and half the amplitude of the last, normalised to about `[-1, 1]`.
+    pub fn fbm(&self, p: &Vec3, octaves: u32) -> f32 {
+        self.octaves(p, octaves, |q| self.perlin(q))
+    }
+
+    /// Like [`Noise::fbm`], but summing the magnitude of each layer, which gives sharp
+    /// creases where the noise crosses zero. In `[0, 1]`.
+    pub fn turbulence(&self, p: &Vec3, octaves: u32) -> f32 {
+        self.octaves(p, octaves, |q| self.perlin(q).abs())
+    }
+
+    /// Layered Worley noise, clamped to `[0, 1]`.
+    pub fn cellular(&self, p: &Vec3, octaves: u32) -> f32 {
+        self.octaves(p, octaves, |q| self.worley(q)).min(1.0)
+    }
+
+    fn octaves(&self, p: &Vec3, octaves: u32, layer: impl Fn(&Vec3) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * layer(&(p * frequency));
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total_amplitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Noise;
+    use crate::sampler::Sampler;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn noise_is_seeded_and_bounded() {
+        let noise = Noise::new(5);
+        let same = Noise::new(5);
+        let other = Noise::new(6);
+        let mut sampler = Sampler::new(1);
+        let mut differs = false;
+        for _ in 0..1000 {
+            let p = Vec3::random_in_range(&mut sampler, -50.0, 50.0);
+            let value = noise.perlin(&p);
+            assert_eq!(value, same.perlin(&p));
+            assert!((-1.0..=1.0).contains(&value), "{value}");
+            assert!((0.0..=1.0).contains(&noise.turbulence(&p, 5)));
+            assert!((0.0..=1.0).contains(&noise.cellular(&p, 3)));
+            differs |= value != other.perlin(&p);
+        }
+        assert!(differs);
+        assert_eq!(noise.perlin(&Vec3::new(3.0, -2.0, 7.0)), 0.0);
+    }
+}
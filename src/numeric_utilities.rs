@@ -1,10 +1,19 @@
-use rand::distributions::Standard;
-use rand::prelude::*;
-
-pub fn linear_to_gamma(linear: f32) -> f32 {
-    linear.sqrt()
+/// The sRGB opto-electronic transfer function, mapping linear light in `[0, 1]` to the
+/// encoded value stored in an 8-bit image.
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
 }
 
-pub fn get_rand_float() -> f32 {
-    rand::thread_rng().sample(Standard)
+/// The inverse of [`linear_to_srgb`], decoding a value read from an 8-bit image to linear
+/// light.
+pub fn srgb_to_linear(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
 }
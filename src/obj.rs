@@ -0,0 +1,631 @@
+//! Loading of Wavefront OBJ meshes and their MTL material libraries.
+//!
+//! Each `usemtl` group of an OBJ file becomes one [`TriangleMesh`]. Polygons are
+//! triangulated as fans, and MTL materials are mapped onto the renderer's own materials:
+//! transparent materials become [`Dielectric`] (`Ni` is the refractive index), materials
+//! with a reflective illumination model become [`Metal`] (`Kd` is the albedo and `Ns` sets
+//! the fuzz), materials with a non-black `Ke` become [`DiffuseLight`], and everything else
+//! is [`Lambertian`] with `Kd` as its albedo. Each MTL material is built once and shared by
+//! every face that uses it. Faces with no area are dropped. A missing MTL library is
+//! returned as an [`ObjWarning`] rather than an error, and the materials that aren't in
+//! any library found then fall back to the default one, each with a warning of its own.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::triangle::{MeshVertices, TriangleMesh};
+use crate::vec3::Vec3;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// A problem in an OBJ file that loading worked around, for the caller to report.
+#[derive(Debug, Clone)]
+pub struct ObjWarning {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+/// The meshes of an OBJ file, one per material, and the problems worked around in
+/// loading them.
+pub struct ObjMeshes {
+    pub meshes: Vec<TriangleMesh>,
+    pub warnings: Vec<ObjWarning>,
+}
+
+/// Loads an OBJ file, along with any MTL libraries it references, as one mesh per
+/// material.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjMeshes, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj(
+        &source,
+        path,
+        |library| {
+            let library_path = directory.join(library);
+            match fs::read_to_string(&library_path) {
+                Ok(library_source) => parse_mtl(&library_source, &library_path).map(Some),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(source) => Err(ObjError::Io {
+                    path: library_path,
+                    source,
+                }),
+            }
+        },
+        None,
+    )
+}
+
+/// Loads an OBJ file with `material` on every face in place of its own materials, which
+/// are then neither loaded nor checked.
+pub fn load_obj_with_material(
+    path: impl AsRef<Path>,
+    material: Arc<dyn Material>,
+) -> Result<ObjMeshes, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    parse_obj(&source, path, |_| Ok(Some(HashMap::new())), Some(material))
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[derive(Clone)]
+struct MtlMaterial {
+    diffuse: Vec3,
+    emission: Vec3,
+    shininess: f32,
+    refractive_index: f32,
+    dissolve: f32,
+    illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Vec3::new(0.5, 0.5, 0.5),
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refractive_index: 1.5,
+            dissolve: 1.0,
+            illumination: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn is_transparent(&self) -> bool {
+        self.dissolve < 1.0 || matches!(self.illumination, 4 | 6 | 7 | 9)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.emission.x > 0.0 || self.emission.y > 0.0 || self.emission.z > 0.0
+    }
+
+    fn is_reflective(&self) -> bool {
+        matches!(self.illumination, 3 | 5 | 8)
+    }
+
+    // Converts a Phong exponent to a roughness, via the usual Beckmann equivalence.
+    fn fuzz(&self) -> f32 {
+        (2.0 / (self.shininess.max(0.0) + 2.0))
+            .sqrt()
+            .clamp(0.0, 1.0)
+    }
+
+    fn to_material(&self) -> Arc<dyn Material> {
+        if self.is_emissive() {
+            Arc::new(DiffuseLight::new(self.emission.clone()))
+        } else if self.is_transparent() {
+            Arc::new(Dielectric::new(self.refractive_index))
+        } else if self.is_reflective() {
+            Arc::new(Metal::new(self.diffuse.clone(), self.fuzz()))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse.clone()))
+        }
+    }
+}
+
+struct LineParser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl LineParser<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn warning(&self, message: impl Into<String>) -> ObjWarning {
+        ObjWarning {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn float(&self, token: Option<&str>, what: &str) -> Result<f32, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {what}")))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {what} '{token}'")))
+    }
+
+    fn vec3<'t>(&self, tokens: &mut impl Iterator<Item = &'t str>) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(
+            self.float(tokens.next(), "x component")?,
+            self.float(tokens.next(), "y component")?,
+            self.float(tokens.next(), "z component")?,
+        ))
+    }
+
+    fn name<'t>(
+        &self,
+        tokens: impl Iterator<Item = &'t str>,
+        what: &str,
+    ) -> Result<String, ObjError> {
+        let name = tokens.collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            Err(self.error(format!("missing {what}")))
+        } else {
+            Ok(name)
+        }
+    }
+
+    /// Resolves a 1-based (or negative, relative) OBJ index against a buffer length.
+    fn index(&self, token: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let value: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {what} index '{token}'")))?;
+        let resolved = match value {
+            v if v > 0 => v - 1,
+            v if v < 0 => len as i64 + v,
+            _ => return Err(self.error(format!("{what} indices start at 1"))),
+        };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{what} index {value} is out of range")));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+fn parse_mtl(source: &str, path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (number, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: number + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        if keyword.starts_with('#') {
+            continue;
+        }
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((
+                parser.name(tokens, "material name")?,
+                MtlMaterial::default(),
+            ));
+            continue;
+        }
+        let Some((_, material)) = current.as_mut() else {
+            return Err(parser.error(format!("'{keyword}' before any newmtl")));
+        };
+        match keyword {
+            "Kd" => material.diffuse = parser.vec3(&mut tokens)?,
+            "Ke" => material.emission = parser.vec3(&mut tokens)?,
+            "Ns" => material.shininess = parser.float(tokens.next(), "Ns")?,
+            "Ni" => material.refractive_index = parser.float(tokens.next(), "Ni")?,
+            "d" => material.dissolve = parser.float(tokens.next(), "d")?,
+            "Tr" => material.dissolve = 1.0 - parser.float(tokens.next(), "Tr")?,
+            "illum" => {
+                let token = tokens.next().unwrap_or_default();
+                material.illumination = token
+                    .parse()
+                    .map_err(|_| parser.error(format!("invalid illum '{token}'")))?;
+            }
+            // Colours, maps and options the renderer has no use for.
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+#[derive(Default)]
+struct MeshGroup {
+    vertex_lookup: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    keys: Vec<(usize, Option<usize>, Option<usize>)>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshGroup {
+    fn vertex(&mut self, key: (usize, Option<usize>, Option<usize>)) -> usize {
+        *self.vertex_lookup.entry(key).or_insert_with(|| {
+            self.keys.push(key);
+            self.keys.len() - 1
+        })
+    }
+
+    fn into_vertices(
+        self,
+        positions: &[Vec3],
+        uvs: &[(f32, f32)],
+        normals: &[Vec3],
+    ) -> (MeshVertices, Vec<[usize; 3]>) {
+        let vertices = MeshVertices {
+            positions: self.keys.iter().map(|k| positions[k.0].clone()).collect(),
+            uvs: self
+                .keys
+                .iter()
+                .map(|k| k.1.map(|i| uvs[i]))
+                .collect::<Option<Vec<_>>>(),
+            normals: self
+                .keys
+                .iter()
+                .map(|k| k.2.map(|i| normals[i].clone()))
+                .collect::<Option<Vec<_>>>(),
+        };
+        (vertices, self.indices)
+    }
+}
+
+fn parse_obj(
+    source: &str,
+    path: &Path,
+    mut load_library: impl FnMut(&str) -> Result<Option<HashMap<String, MtlMaterial>>, ObjError>,
+    material: Option<Arc<dyn Material>>,
+) -> Result<ObjMeshes, ObjError> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut materials = HashMap::new();
+    let mut missing_libraries = Vec::new();
+    let mut warnings = Vec::new();
+    let mut groups: Vec<(Option<String>, MeshGroup)> = vec![(None, MeshGroup::default())];
+    let mut current_group = 0;
+
+    for (number, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: number + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        match keyword {
+            "v" => positions.push(parser.vec3(&mut tokens)?),
+            "vt" => {
+                let u = parser.float(tokens.next(), "u coordinate")?;
+                let v = match tokens.next() {
+                    Some(token) => parser.float(Some(token), "v coordinate")?,
+                    None => 0.0,
+                };
+                uvs.push((u, v));
+            }
+            "vn" => {
+                let normal = parser.vec3(&mut tokens)?;
+                if normal.length_squared() == 0.0 {
+                    return Err(parser.error("normals can't have zero length"));
+                }
+                normals.push(normal.unit_vector());
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                let mut corner_positions = Vec::new();
+                for corner in tokens {
+                    let mut parts = corner.split('/');
+                    let position =
+                        parser.index(parts.next().unwrap(), positions.len(), "vertex")?;
+                    let uv = match parts.next() {
+                        Some("") | None => None,
+                        Some(token) => Some(parser.index(token, uvs.len(), "texture")?),
+                    };
+                    let normal = match parts.next() {
+                        Some("") | None => None,
+                        Some(token) => Some(parser.index(token, normals.len(), "normal")?),
+                    };
+                    corners.push(groups[current_group].1.vertex((position, uv, normal)));
+                    corner_positions.push(position);
+                }
+                if corners.len() < 3 {
+                    return Err(parser.error("faces need at least three vertices"));
+                }
+                for i in 1..corners.len() - 1 {
+                    let [a, b, c] = [0, i, i + 1].map(|j| &positions[corner_positions[j]]);
+                    // A triangle with no area has no normal to shade with.
+                    if (b - a).cross(&(c - a)).length_squared() == 0.0 {
+                        continue;
+                    }
+                    groups[current_group]
+                        .1
+                        .indices
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "mtllib" => {
+                for library in tokens {
+                    match load_library(library)? {
+                        Some(library) => materials.extend(library),
+                        None => {
+                            warnings.push(
+                                parser.warning(format!("material library '{library}' not found")),
+                            );
+                            missing_libraries.push(library);
+                        }
+                    }
+                }
+            }
+            "usemtl" => {
+                let name = parser.name(tokens, "material name")?;
+                let group = groups.iter().position(|(n, _)| n.as_ref() == Some(&name));
+                // A name missing from every library found may be in one that wasn't, but
+                // with no library missing it can only be a mistake.
+                if material.is_none() && !materials.contains_key(&name) {
+                    if missing_libraries.is_empty() {
+                        return Err(parser.error(format!("undefined material '{name}'")));
+                    }
+                    if group.is_none() {
+                        warnings.push(parser.warning(format!(
+                            "material '{name}' is in no library found, so may be in {}; using \
+                             the default material",
+                            missing_libraries.join(" or ")
+                        )));
+                    }
+                }
+                current_group = match group {
+                    Some(index) => index,
+                    None => {
+                        groups.push((Some(name), MeshGroup::default()));
+                        groups.len() - 1
+                    }
+                };
+            }
+            // Comments, object and smoothing groups, and geometry such as lines and curves
+            // that has no surface to render.
+            _ => {}
+        }
+    }
+
+    let default_material = material.unwrap_or_else(|| MtlMaterial::default().to_material());
+    let materials: HashMap<String, Arc<dyn Material>> = materials
+        .iter()
+        .map(|(name, material)| (name.clone(), material.to_material()))
+        .collect();
+    let meshes = groups
+        .into_iter()
+        .filter(|(_, group)| !group.indices.is_empty())
+        .map(|(name, group)| {
+            let material = name
+                .and_then(|name| materials.get(&name))
+                .unwrap_or(&default_material);
+            let (vertices, indices) = group.into_vertices(&positions, &uvs, &normals);
+            TriangleMesh::new(vertices, indices, material.clone())
+        })
+        .collect();
+    Ok(ObjMeshes { meshes, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_mtl, parse_obj, ObjError};
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::material::{Lambertian, Material};
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const MTL: &str = "
+newmtl matte
+Kd 0.8 0.1 0.1
+newmtl glass
+Ni 1.45
+d 0.1
+";
+
+    const QUAD: &str = "
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+usemtl matte
+f 1/1/1 2/2/1 3/3/1 4/4/1
+usemtl glass
+f -4//1 -2//1 -1//1
+";
+
+    fn parse(source: &str) -> Result<Vec<crate::triangle::TriangleMesh>, ObjError> {
+        parse_obj(
+            source,
+            Path::new("test.obj"),
+            |_| parse_mtl(MTL, Path::new("scene.mtl")).map(Some),
+            None,
+        )
+        .map(|loaded| loaded.meshes)
+    }
+
+    #[test]
+    fn obj_groups_faces_by_material() {
+        let meshes = parse(QUAD).unwrap();
+        assert_eq!(meshes.len(), 2);
+        let r = Ray::new(Vec3::new(0.75, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = meshes[0]
+            .hit(&r, &Interval::new(0.001, f32::INFINITY))
+            .unwrap();
+        assert_eq!(hr.impact.t, 1.0);
+        assert_eq!(hr.impact.normal.z, 1.0);
+    }
+
+    #[test]
+    fn obj_reports_malformed_lines() {
+        let error = parse("v 0 0 0\nv 1 0 zero\n").err().unwrap();
+        assert!(matches!(error, ObjError::Parse { line: 2, .. }));
+        let error = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").err().unwrap();
+        assert!(matches!(error, ObjError::Parse { line: 3, .. }));
+        let error = parse("usemtl missing\n").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.obj:1: undefined material 'missing'"
+        );
+        let error = parse("vn 0 0 1\nvn 0 0 0\n").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "test.obj:2: normals can't have zero length"
+        );
+    }
+
+    #[test]
+    fn obj_material_replaces_mtl_materials() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.1, 0.2, 0.3)));
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n";
+        let meshes = parse_obj(
+            source,
+            Path::new("test.obj"),
+            |_| panic!("no library should be loaded"),
+            Some(material.clone()),
+        )
+        .unwrap()
+        .meshes;
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(Arc::strong_count(&material), 2);
+
+        let r = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = meshes[0]
+            .hit(&r, &Interval::new(0.001, f32::INFINITY))
+            .unwrap();
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let reflected = hr.material.eval(&r, &hr.impact, &up);
+        let expected = material.eval(&r, &hr.impact, &up);
+        assert!(!expected.is_near_zero());
+        assert_eq!(
+            (reflected.x, reflected.y, reflected.z),
+            (expected.x, expected.y, expected.z)
+        );
+    }
+
+    #[test]
+    fn obj_tolerates_missing_libraries_and_degenerate_faces() {
+        let source = "
+mtllib missing.mtl scene.mtl
+v 0 0 0
+v 1 0 0
+v 2 0 0
+v 0 1 0
+usemtl line
+f 1 2 3
+usemtl matte
+f 1 2 4
+usemtl shiny
+usemtl line
+";
+        let load_library = |library: &str| match library {
+            "scene.mtl" => parse_mtl(MTL, Path::new("scene.mtl")).map(Some),
+            _ => Ok(None),
+        };
+        let loaded = parse_obj(source, Path::new("test.obj"), load_library, None).unwrap();
+        let warnings: Vec<_> = loaded.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "test.obj:2: material library 'missing.mtl' not found",
+                "test.obj:7: material 'line' is in no library found, so may be in \
+                 missing.mtl; using the default material",
+                "test.obj:11: material 'shiny' is in no library found, so may be in \
+                 missing.mtl; using the default material",
+            ]
+        );
+        let meshes = loaded.meshes;
+        assert_eq!(meshes.len(), 1);
+        let r = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = meshes[0]
+            .hit(&r, &Interval::new(0.001, f32::INFINITY))
+            .unwrap();
+        assert_eq!(hr.impact.normal.z, 1.0);
+
+        // Without a missing library, an unknown name is still a mistake.
+        let source = "mtllib scene.mtl\nusemtl mate\n";
+        let error = parse_obj(source, Path::new("test.obj"), load_library, None)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "test.obj:2: undefined material 'mate'");
+    }
+
+    #[test]
+    fn mtl_requires_newmtl_first() {
+        let error = parse_mtl("Kd 1 1 1\n", Path::new("bad.mtl")).err().unwrap();
+        assert!(matches!(error, ObjError::Parse { line: 1, .. }));
+        assert_eq!(parse_mtl(MTL, Path::new("scene.mtl")).unwrap().len(), 2);
+    }
+}
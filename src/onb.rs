@@ -0,0 +1,47 @@
+use crate::vec3::Vec3;
+
+/// An orthonormal basis, for working with directions relative to a normal or an axis.
+#[derive(Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// A basis whose `w` axis points along `n`.
+    pub fn from_w(n: &Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).unit_vector();
+        let u = w.cross(&v);
+        Onb { u, v, w }
+    }
+
+    /// A basis whose `w` axis points along `n` and whose `u` axis is as close to `u` as
+    /// possible. Falls back to [`Onb::from_w`] if `u` is parallel to `n`.
+    pub fn from_w_u(n: &Vec3, u: &Vec3) -> Self {
+        let w = n.unit_vector();
+        let u = u - &w * u.dot(&w);
+        if u.length_squared() < 1e-12 {
+            return Onb::from_w(&w);
+        }
+        let u = u.unit_vector();
+        let v = w.cross(&u);
+        Onb { u, v, w }
+    }
+
+    /// Converts coordinates in this basis to world space.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        &self.u * a.x + &self.v * a.y + &self.w * a.z
+    }
+
+    /// Converts world-space coordinates to this basis.
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
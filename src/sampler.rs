@@ -0,0 +1,44 @@
+use rand::distributions::{Standard, Uniform};
+use rand::prelude::*;
+use rand_pcg::Pcg32;
+
+/// A seedable source of random numbers.
+///
+/// Every random draw in the renderer goes through a `Sampler` so that a render can be
+/// reproduced exactly from its seed. Each pixel gets its own stream, derived from the
+/// render seed and the pixel coordinates, so the result does not depend on the order in
+/// which pixels are rendered or on how many threads render them.
+pub struct Sampler {
+    rng: Pcg32,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        Sampler {
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    pub fn for_pixel(seed: u64, x: u32, y: u32) -> Self {
+        let stream = ((y as u64) << 32) | x as u64;
+        Sampler {
+            rng: Pcg32::new(Self::mix(seed), stream),
+        }
+    }
+
+    pub fn get_float(&mut self) -> f32 {
+        self.rng.sample(Standard)
+    }
+
+    pub fn get_float_in_range(&mut self, min: f32, max: f32) -> f32 {
+        self.rng.sample(Uniform::new_inclusive(min, max))
+    }
+
+    // SplitMix64 finaliser, so that nearby seeds give unrelated starting states.
+    fn mix(seed: u64) -> u64 {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,36 @@
+use crate::aabb::Aabb;
+use crate::environment::{Environment, Gradient};
+use crate::hittable::Hittable;
+
+/// Everything the camera renders: the objects in the world and the environment around
+/// them.
+///
+/// `lights` holds the emissive parts of the world, gathered through
+/// [`Hittable::collect_lights`], for the integrator to sample directly. Emitters that
+/// can't be sampled are still found by chance, just with more noise. `bounds` is the
+/// bounding box of the world, found once so that integrators needn't ask for it per ray.
+pub struct Scene {
+    pub world: Box<dyn Hittable>,
+    pub bounds: Aabb,
+    pub environment: Box<dyn Environment>,
+    pub lights: Vec<Box<dyn Hittable>>,
+}
+
+impl Scene {
+    /// A scene under the default sky [`Gradient`].
+    pub fn new(world: impl Hittable + 'static) -> Self {
+        let mut lights = Vec::new();
+        world.collect_lights(&mut lights);
+        Scene {
+            bounds: world.bounding_box(),
+            world: Box::new(world),
+            environment: Box::new(Gradient::default()),
+            lights,
+        }
+    }
+
+    pub fn with_environment(mut self, environment: impl Environment + 'static) -> Self {
+        self.environment = Box::new(environment);
+        self
+    }
+}
@@ -0,0 +1,1140 @@
+//! A TOML description of a scene: the camera, render settings, named materials and the
+//! objects that use them.
+//!
+//! ```toml
+//! [camera]
+//! image_width = 400
+//! aspect_ratio = 1.777
+//! look_from = [13.0, 2.0, 3.0]
+//!
+//! [render]
+//! pixel_samples = 100
+//! seed = 7
+//! tone_mapper = "aces"
+//!
+//! [environment]
+//! type = "image"
+//! path = "sky.hdr"
+//! rotation = 90.0
+//!
+//! [textures.checks]
+//! type = "checker"
+//! even = [0.2, 0.3, 0.1]
+//! odd = [0.9, 0.9, 0.9]
+//! scale = 0.5
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! texture = "checks"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//!
+//! [[objects]]
+//! type = "mesh"
+//! path = "teapot.obj"
+//! ```
+//!
+//! Every camera and render setting is optional and falls back to the [`CameraBuilder`]
+//! default. Without an `[environment]` the scene is lit by the default sky [`Gradient`].
+//! Lambertian and metal materials take either a constant `albedo` or the name of a
+//! `texture`. A metal's `fuzz` and a conductor's `roughness` and `roughness_v` can
+//! likewise be replaced by a `fuzz_texture`, `roughness_texture` and
+//! `roughness_v_texture`, which read the mean of the texture's channels. Any material can
+//! name a `normal_map` or a `bump` height texture. Mesh and image paths are relative to
+//! the scene file. Numbers out of range, such as a zero `image_width` or a negative
+//! `radius`, and fields that a table's `type` doesn't use are errors.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::ImageError;
+use serde::{de, Deserialize, Deserializer};
+use toml::Spanned;
+
+use crate::bvh::Bvh;
+use crate::camera::{Camera, CameraBuilder, CameraError};
+use crate::environment::{EnvironmentMap, Gradient, SolidColor};
+use crate::hittable::{Hittable, Sphere};
+use crate::integrator::IntegratorMode;
+use crate::material::{
+    BumpMapped, Conductor, Dielectric, DiffuseLight, Lambertian, Material, Metal,
+};
+use crate::obj::{self, ObjError, ObjWarning};
+use crate::scene::Scene;
+use crate::texture::{
+    ConstantColor, ImageTexture, NoisePattern, NoiseTexture, ScalarTexture, SpatialChecker,
+    Texture, UvChecker, WrapMode,
+};
+use crate::tonemap::{ToneMapOperator, ToneMapping};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        field: Option<String>,
+        message: String,
+    },
+    Obj(ObjError),
+    Camera(CameraError),
+    Image {
+        path: PathBuf,
+        source: ImageError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Invalid {
+                path,
+                line,
+                column,
+                field,
+                message,
+            } => {
+                write!(f, "{}:{}:{}: ", path.display(), line, column)?;
+                if let Some(field) = field {
+                    write!(f, "{}: ", field)?;
+                }
+                write!(f, "{}", message)
+            }
+            SceneError::Obj(error) => error.fmt(f),
+            SceneError::Camera(error) => error.fmt(f),
+            SceneError::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Invalid { .. } => None,
+            SceneError::Obj(error) => Some(error),
+            SceneError::Camera(error) => Some(error),
+            SceneError::Image { source, .. } => Some(source),
+        }
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(error: ObjError) -> Self {
+        SceneError::Obj(error)
+    }
+}
+
+impl From<CameraError> for SceneError {
+    fn from(error: CameraError) -> Self {
+        SceneError::Camera(error)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    #[serde(default, deserialize_with = "positive")]
+    image_width: Option<u32>,
+    #[serde(default, deserialize_with = "positive")]
+    aspect_ratio: Option<f32>,
+    #[serde(default, deserialize_with = "field_of_view")]
+    vert_fov: Option<f32>,
+    #[serde(default, deserialize_with = "non_negative")]
+    defocus_angle: Option<f32>,
+    #[serde(default, deserialize_with = "positive")]
+    focal_dist: Option<f32>,
+    look_from: Option<[f32; 3]>,
+    look_at: Option<[f32; 3]>,
+    v_up: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RenderDescription {
+    #[serde(default, deserialize_with = "positive")]
+    pixel_samples: Option<u32>,
+    max_ray_depth: Option<u32>,
+    roulette_depth: Option<u32>,
+    seed: Option<u64>,
+    threads: Option<usize>,
+    integrator: Option<IntegratorMode>,
+    tone_mapper: Option<ToneMapOperator>,
+    #[serde(default, deserialize_with = "finite")]
+    exposure: Option<f32>,
+    #[serde(default, deserialize_with = "positive")]
+    white_point: Option<f32>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum EnvironmentKind {
+    Solid,
+    Gradient,
+    /// An equirectangular image, normally HDR.
+    Image,
+}
+
+/// What rays that leave the scene see. Which of the optional fields are needed depends on
+/// the `type`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDescription {
+    #[serde(rename = "type")]
+    kind: EnvironmentKind,
+    color: Option<[f32; 3]>,
+    bottom: Option<[f32; 3]>,
+    top: Option<[f32; 3]>,
+    path: Option<PathBuf>,
+    /// Degrees about the y axis.
+    #[serde(default, deserialize_with = "finite")]
+    rotation: Option<f32>,
+    #[serde(default, deserialize_with = "non_negative")]
+    intensity: Option<f32>,
+}
+
+impl EnvironmentDescription {
+    fn set_fields(&self) -> Vec<&'static str> {
+        set_fields([
+            ("color", self.color.is_some()),
+            ("bottom", self.bottom.is_some()),
+            ("top", self.top.is_some()),
+            ("path", self.path.is_some()),
+            ("rotation", self.rotation.is_some()),
+            ("intensity", self.intensity.is_some()),
+        ])
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum TextureKind {
+    /// A checkerboard of cubes of side `scale` filling space.
+    Checker,
+    /// A checkerboard of `columns` by `rows` squares in texture space.
+    UvChecker,
+    Image,
+    /// Procedural noise, blending from `low` to `high` following its `pattern`.
+    Noise,
+}
+
+/// A named texture. Which of the optional fields are needed depends on the `type`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDescription {
+    #[serde(rename = "type")]
+    kind: TextureKind,
+    even: Option<[f32; 3]>,
+    odd: Option<[f32; 3]>,
+    /// The side of a checker's cubes, or the frequency of noise.
+    #[serde(default, deserialize_with = "positive")]
+    scale: Option<f32>,
+    #[serde(default, deserialize_with = "positive")]
+    columns: Option<f32>,
+    #[serde(default, deserialize_with = "positive")]
+    rows: Option<f32>,
+    path: Option<PathBuf>,
+    wrap: Option<WrapMode>,
+    /// Whether an image holds data, such as a normal map, rather than sRGB colours.
+    linear: Option<bool>,
+    pattern: Option<NoisePattern>,
+    seed: Option<u64>,
+    octaves: Option<u32>,
+    low: Option<[f32; 3]>,
+    high: Option<[f32; 3]>,
+}
+
+impl TextureDescription {
+    fn set_fields(&self) -> Vec<&'static str> {
+        set_fields([
+            ("even", self.even.is_some()),
+            ("odd", self.odd.is_some()),
+            ("scale", self.scale.is_some()),
+            ("columns", self.columns.is_some()),
+            ("rows", self.rows.is_some()),
+            ("path", self.path.is_some()),
+            ("wrap", self.wrap.is_some()),
+            ("linear", self.linear.is_some()),
+            ("pattern", self.pattern.is_some()),
+            ("seed", self.seed.is_some()),
+            ("octaves", self.octaves.is_some()),
+            ("low", self.low.is_some()),
+            ("high", self.high.is_some()),
+        ])
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum MaterialKind {
+    Lambertian,
+    Metal,
+    Dielectric,
+    DiffuseLight,
+    /// A rough metal, given by a `preset` or by `eta` and `k`.
+    Conductor,
+}
+
+/// Measured metals a `conductor` can be made of.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+}
+
+/// A named material. Which of the optional fields are needed depends on the `type`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDescription {
+    #[serde(rename = "type")]
+    kind: MaterialKind,
+    albedo: Option<[f32; 3]>,
+    /// The name of a texture to use in place of `albedo`.
+    texture: Option<String>,
+    #[serde(default, deserialize_with = "unit_interval")]
+    fuzz: Option<f32>,
+    /// The name of a texture to use in place of `fuzz`.
+    fuzz_texture: Option<String>,
+    #[serde(default, deserialize_with = "positive")]
+    refractive_index: Option<f32>,
+    /// Emitted radiance of a `diffuse_light`.
+    emit: Option<[f32; 3]>,
+    preset: Option<ConductorPreset>,
+    /// The real and imaginary parts of a conductor's refractive index, per channel.
+    eta: Option<[f32; 3]>,
+    k: Option<[f32; 3]>,
+    /// A conductor's roughness, along `dpdu` if `roughness_v` is also given.
+    #[serde(default, deserialize_with = "unit_interval")]
+    roughness: Option<f32>,
+    #[serde(default, deserialize_with = "unit_interval")]
+    roughness_v: Option<f32>,
+    /// The names of textures to use in place of `roughness` and `roughness_v`.
+    roughness_texture: Option<String>,
+    roughness_v_texture: Option<String>,
+    /// The name of a tangent-space normal map texture.
+    normal_map: Option<String>,
+    /// The name of a height texture, used when there is no `normal_map`.
+    bump: Option<String>,
+    /// The height of the bumps where the `bump` texture is white. Defaults to one.
+    #[serde(default, deserialize_with = "finite")]
+    bump_strength: Option<f32>,
+}
+
+impl MaterialDescription {
+    fn set_fields(&self) -> Vec<&'static str> {
+        set_fields([
+            ("albedo", self.albedo.is_some()),
+            ("texture", self.texture.is_some()),
+            ("fuzz", self.fuzz.is_some()),
+            ("fuzz_texture", self.fuzz_texture.is_some()),
+            ("refractive_index", self.refractive_index.is_some()),
+            ("emit", self.emit.is_some()),
+            ("preset", self.preset.is_some()),
+            ("eta", self.eta.is_some()),
+            ("k", self.k.is_some()),
+            ("roughness", self.roughness.is_some()),
+            ("roughness_v", self.roughness_v.is_some()),
+            ("roughness_texture", self.roughness_texture.is_some()),
+            ("roughness_v_texture", self.roughness_v_texture.is_some()),
+            ("normal_map", self.normal_map.is_some()),
+            ("bump", self.bump.is_some()),
+            ("bump_strength", self.bump_strength.is_some()),
+        ])
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ObjectKind {
+    Sphere,
+    Triangle,
+    /// A Wavefront OBJ file, which brings its own materials unless `material` names one
+    /// for all of its faces.
+    Mesh,
+}
+
+/// An object in the world. Which of the optional fields are needed depends on the `type`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDescription {
+    #[serde(rename = "type")]
+    kind: ObjectKind,
+    center: Option<[f32; 3]>,
+    #[serde(default, deserialize_with = "positive")]
+    radius: Option<f32>,
+    vertices: Option<[[f32; 3]; 3]>,
+    material: Option<String>,
+    path: Option<PathBuf>,
+}
+
+impl ObjectDescription {
+    fn set_fields(&self) -> Vec<&'static str> {
+        set_fields([
+            ("center", self.center.is_some()),
+            ("radius", self.radius.is_some()),
+            ("vertices", self.vertices.is_some()),
+            ("material", self.material.is_some()),
+            ("path", self.path.is_some()),
+        ])
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default)]
+    camera: CameraDescription,
+    #[serde(default)]
+    render: RenderDescription,
+    environment: Option<Spanned<EnvironmentDescription>>,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDescription>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDescription>>,
+}
+
+type Objects = Vec<Box<dyn Hittable>>;
+
+/// A scene description together with where it came from, so that errors can point back
+/// into the file.
+pub struct SceneFile {
+    description: SceneDescription,
+    path: PathBuf,
+    source: String,
+}
+
+/// Loads a scene file and builds its camera and scene, along with any warnings from
+/// loading its meshes.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<(Camera, Scene, Vec<ObjWarning>), SceneError> {
+    let scene = SceneFile::load(path)?;
+    let (world, warnings) = scene.build_scene()?;
+    Ok((scene.camera_builder().build()?, world, warnings))
+}
+
+impl SceneFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(source, path)
+    }
+
+    pub fn parse(source: String, path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref().to_path_buf();
+        match toml::from_str(&source) {
+            Ok(description) => Ok(SceneFile {
+                description,
+                path,
+                source,
+            }),
+            Err(error) => {
+                let offset = error.span().map_or(0, |span| span.start);
+                let (line, column) = Self::position(&source, offset);
+                Err(SceneError::Invalid {
+                    path,
+                    line,
+                    column,
+                    field: Self::key_at(&source, offset),
+                    message: error.message().to_string(),
+                })
+            }
+        }
+    }
+
+    /// A camera builder with every setting the file gives applied, ready for further
+    /// overrides.
+    pub fn camera_builder(&self) -> CameraBuilder {
+        let camera = &self.description.camera;
+        let render = &self.description.render;
+        let mut builder = CameraBuilder::new(400, 16.0 / 9.0);
+        if let Some(image_width) = camera.image_width {
+            builder = builder.image_width(image_width);
+        }
+        if let Some(aspect_ratio) = camera.aspect_ratio {
+            builder = builder.aspect_ratio(aspect_ratio);
+        }
+        if let Some(vert_fov) = camera.vert_fov {
+            builder = builder.vert_fov(vert_fov);
+        }
+        if let Some(defocus_angle) = camera.defocus_angle {
+            builder = builder.defocus_angle(defocus_angle);
+        }
+        if let Some(focal_dist) = camera.focal_dist {
+            builder = builder.focal_dist(focal_dist);
+        }
+        if let Some(look_from) = camera.look_from {
+            builder = builder.look_from(vec3(look_from));
+        }
+        if let Some(look_at) = camera.look_at {
+            builder = builder.look_at(vec3(look_at));
+        }
+        if let Some(v_up) = camera.v_up {
+            builder = builder.v_up(vec3(v_up));
+        }
+        if let Some(pixel_samples) = render.pixel_samples {
+            builder = builder.pixel_samples(pixel_samples);
+        }
+        if let Some(max_ray_depth) = render.max_ray_depth {
+            builder = builder.max_ray_depth(max_ray_depth);
+        }
+        if let Some(roulette_depth) = render.roulette_depth {
+            builder = builder.roulette_depth(roulette_depth);
+        }
+        if let Some(seed) = render.seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(threads) = render.threads {
+            builder = builder.threads(threads);
+        }
+        if let Some(integrator) = render.integrator {
+            builder = builder.integrator(integrator);
+        }
+        builder
+    }
+
+    /// The tone mapping to use when saving a low-dynamic-range image of the scene.
+    pub fn tone_mapping(&self) -> ToneMapping {
+        let render = &self.description.render;
+        let defaults = ToneMapping::default();
+        ToneMapping {
+            operator: render.tone_mapper.unwrap_or(defaults.operator),
+            exposure: render.exposure.unwrap_or(defaults.exposure),
+            white_point: render.white_point.unwrap_or(defaults.white_point),
+        }
+    }
+
+    /// The world together with its environment, and the warnings from loading its
+    /// meshes. Objects made of a `diffuse_light`, including emissive faces of meshes, are
+    /// also sampled as the scene's lights.
+    pub fn build_scene(&self) -> Result<(Scene, Vec<ObjWarning>), SceneError> {
+        let (world, warnings) = self.build_world()?;
+        let scene = Scene::new(world);
+        let Some(environment) = &self.description.environment else {
+            return Ok((scene, warnings));
+        };
+        let description = environment.get_ref();
+        let field = |name: &str| format!("environment.{}", name);
+        self.only_fields(
+            environment,
+            field,
+            description.set_fields(),
+            match description.kind {
+                EnvironmentKind::Solid => &["color"],
+                EnvironmentKind::Gradient => &["bottom", "top"],
+                EnvironmentKind::Image => &["path", "rotation", "intensity"],
+            },
+        )?;
+        let scene = match description.kind {
+            EnvironmentKind::Solid => {
+                let color = self.required(environment, &field("color"), &description.color)?;
+                scene.with_environment(SolidColor::new(vec3(*color)))
+            }
+            EnvironmentKind::Gradient => {
+                let bottom = self.required(environment, &field("bottom"), &description.bottom)?;
+                let top = self.required(environment, &field("top"), &description.top)?;
+                scene.with_environment(Gradient::new(vec3(*bottom), vec3(*top)))
+            }
+            EnvironmentKind::Image => {
+                let path = self.required(environment, &field("path"), &description.path)?;
+                let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                let path = directory.join(path);
+                let map = EnvironmentMap::load(&path)
+                    .map_err(|source| SceneError::Image { path, source })?
+                    .rotation(description.rotation.unwrap_or(0.0))
+                    .intensity(description.intensity.unwrap_or(1.0));
+                scene.with_environment(map)
+            }
+        };
+        Ok((scene, warnings))
+    }
+
+    /// The objects of the scene, and the warnings from loading its meshes.
+    pub fn build_world(&self) -> Result<(Bvh, Vec<ObjWarning>), SceneError> {
+        let mut warnings = Vec::new();
+        let objects = self.build_objects(&mut warnings)?;
+        Ok((Bvh::new(objects), warnings))
+    }
+
+    fn build_objects(&self, warnings: &mut Vec<ObjWarning>) -> Result<Objects, SceneError> {
+        // Every object naming a material shares one instance of it, and likewise for
+        // materials naming a texture.
+        let mut textures = HashMap::new();
+        for (name, texture) in &self.description.textures {
+            textures.insert(name.as_str(), self.build_texture(name, texture)?);
+        }
+        let mut materials = HashMap::new();
+        for (name, material) in &self.description.materials {
+            materials.insert(
+                name.as_str(),
+                self.build_material(name, material, &textures)?,
+            );
+        }
+
+        let mut world: Objects = Vec::new();
+        for (index, object) in self.description.objects.iter().enumerate() {
+            let field = |name: &str| format!("objects[{}].{}", index, name);
+            let description = object.get_ref();
+            self.only_fields(
+                object,
+                field,
+                description.set_fields(),
+                match description.kind {
+                    ObjectKind::Sphere => &["center", "radius", "material"],
+                    ObjectKind::Triangle => &["vertices", "material"],
+                    ObjectKind::Mesh => &["path", "material"],
+                },
+            )?;
+            let material = || -> Result<Arc<dyn Material>, SceneError> {
+                let name = self.required(object, &field("material"), &description.material)?;
+                materials.get(name.as_str()).cloned().ok_or_else(|| {
+                    self.invalid(
+                        self.field_offset(object, "material"),
+                        field("material"),
+                        format!("undefined material '{}'", name),
+                    )
+                })
+            };
+            match description.kind {
+                ObjectKind::Sphere => {
+                    let center =
+                        vec3(*self.required(object, &field("center"), &description.center)?);
+                    let radius = *self.required(object, &field("radius"), &description.radius)?;
+                    world.push(Box::new(Sphere {
+                        center,
+                        radius,
+                        material: material()?,
+                    }))
+                }
+                ObjectKind::Triangle => {
+                    let vertices =
+                        self.required(object, &field("vertices"), &description.vertices)?;
+                    world.push(Box::new(Triangle {
+                        a: vec3(vertices[0]),
+                        b: vec3(vertices[1]),
+                        c: vec3(vertices[2]),
+                        material: material()?,
+                    }))
+                }
+                ObjectKind::Mesh => {
+                    let path = self.required(object, &field("path"), &description.path)?;
+                    let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                    let path = directory.join(path);
+                    let loaded = match description.material {
+                        Some(_) => obj::load_obj_with_material(path, material()?)?,
+                        None => obj::load_obj(path)?,
+                    };
+                    warnings.extend(loaded.warnings);
+                    world.extend(
+                        loaded
+                            .meshes
+                            .into_iter()
+                            .map(|mesh| Box::new(mesh) as Box<dyn Hittable>),
+                    );
+                }
+            }
+        }
+        Ok(world)
+    }
+
+    fn build_texture(
+        &self,
+        name: &str,
+        texture: &Spanned<TextureDescription>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let field = |field: &str| format!("textures.{}.{}", name, field);
+        let description = texture.get_ref();
+        self.only_fields(
+            texture,
+            field,
+            description.set_fields(),
+            match description.kind {
+                TextureKind::Checker => &["scale", "even", "odd"],
+                TextureKind::UvChecker => &["columns", "rows", "even", "odd"],
+                TextureKind::Image => &["path", "wrap", "linear"],
+                TextureKind::Noise => &["pattern", "seed", "scale", "octaves", "low", "high"],
+            },
+        )?;
+        let colors = || -> Result<(ConstantColor, ConstantColor), SceneError> {
+            let even = self.required(texture, &field("even"), &description.even)?;
+            let odd = self.required(texture, &field("odd"), &description.odd)?;
+            Ok((
+                ConstantColor::new(vec3(*even)),
+                ConstantColor::new(vec3(*odd)),
+            ))
+        };
+        Ok(match description.kind {
+            TextureKind::Checker => {
+                let scale = self.required(texture, &field("scale"), &description.scale)?;
+                let (even, odd) = colors()?;
+                Arc::new(SpatialChecker::new(*scale, even, odd))
+            }
+            TextureKind::UvChecker => {
+                let columns = self.required(texture, &field("columns"), &description.columns)?;
+                let rows = self.required(texture, &field("rows"), &description.rows)?;
+                let (even, odd) = colors()?;
+                Arc::new(UvChecker::new(*columns, *rows, even, odd))
+            }
+            TextureKind::Image => {
+                let path = self.required(texture, &field("path"), &description.path)?;
+                let directory = self.path.parent().unwrap_or_else(|| Path::new(""));
+                let path = directory.join(path);
+                let image = if description.linear.unwrap_or(false) {
+                    ImageTexture::load_data(&path)
+                } else {
+                    ImageTexture::load(&path)
+                };
+                let image = image.map_err(|source| SceneError::Image { path, source })?;
+                Arc::new(image.wrap(description.wrap.unwrap_or_default()))
+            }
+            TextureKind::Noise => {
+                let pattern = self.required(texture, &field("pattern"), &description.pattern)?;
+                let mut noise = NoiseTexture::new(*pattern, description.seed.unwrap_or(0));
+                if let Some(scale) = description.scale {
+                    noise = noise.scale(scale);
+                }
+                if let Some(octaves) = description.octaves {
+                    noise = noise.octaves(octaves);
+                }
+                let low = description.low.unwrap_or([0.0; 3]);
+                let high = description.high.unwrap_or([1.0; 3]);
+                Arc::new(noise.colors(vec3(low), vec3(high)))
+            }
+        })
+    }
+
+    fn build_material(
+        &self,
+        name: &str,
+        material: &Spanned<MaterialDescription>,
+        textures: &HashMap<&str, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let field = |field: &str| format!("materials.{}.{}", name, field);
+        let description = material.get_ref();
+        let used: &[&str] = match description.kind {
+            MaterialKind::Lambertian => &["albedo", "texture"],
+            MaterialKind::Metal => &["albedo", "texture", "fuzz", "fuzz_texture"],
+            MaterialKind::Dielectric => &["refractive_index"],
+            MaterialKind::DiffuseLight => &["emit"],
+            MaterialKind::Conductor => &[
+                "preset",
+                "eta",
+                "k",
+                "roughness",
+                "roughness_v",
+                "roughness_texture",
+                "roughness_v_texture",
+            ],
+        };
+        let bumps = ["normal_map", "bump", "bump_strength"];
+        self.only_fields(
+            material,
+            field,
+            description.set_fields(),
+            &[used, &bumps].concat(),
+        )?;
+        let texture = |key: &str, texture: &String| {
+            textures.get(texture.as_str()).cloned().ok_or_else(|| {
+                self.invalid(
+                    self.field_offset(material, key),
+                    field(key),
+                    format!("undefined texture '{}'", texture),
+                )
+            })
+        };
+        let scalar = |texture: Arc<dyn Texture>| -> Arc<dyn ScalarTexture> { Arc::new(texture) };
+        let albedo = || -> Result<Arc<dyn Texture>, SceneError> {
+            match &description.texture {
+                Some(name) => texture("texture", name),
+                None => {
+                    let albedo = self.required(material, &field("albedo"), &description.albedo)?;
+                    Ok(Arc::new(ConstantColor::new(vec3(*albedo))))
+                }
+            }
+        };
+        let base: Arc<dyn Material> = match description.kind {
+            MaterialKind::Lambertian => Arc::new(Lambertian::textured(albedo()?)),
+            MaterialKind::Metal => {
+                let albedo = albedo()?;
+                let fuzz: Arc<dyn ScalarTexture> = match &description.fuzz_texture {
+                    Some(name) => scalar(texture("fuzz_texture", name)?),
+                    None => {
+                        let fuzz = self.required(material, &field("fuzz"), &description.fuzz)?;
+                        Arc::new(*fuzz)
+                    }
+                };
+                Arc::new(Metal::textured(albedo, 0.0).fuzz_texture(fuzz))
+            }
+            MaterialKind::Dielectric => {
+                let refractive_index = self.required(
+                    material,
+                    &field("refractive_index"),
+                    &description.refractive_index,
+                )?;
+                Arc::new(Dielectric::new(*refractive_index))
+            }
+            MaterialKind::DiffuseLight => {
+                let emit = self.required(material, &field("emit"), &description.emit)?;
+                Arc::new(DiffuseLight::new(vec3(*emit)))
+            }
+            MaterialKind::Conductor => {
+                let conductor = match description.preset {
+                    Some(ConductorPreset::Gold) => Conductor::gold(),
+                    Some(ConductorPreset::Copper) => Conductor::copper(),
+                    Some(ConductorPreset::Aluminium) => Conductor::aluminium(),
+                    None => {
+                        let eta = self.required(material, &field("eta"), &description.eta)?;
+                        let k = self.required(material, &field("k"), &description.k)?;
+                        Conductor::new(vec3(*eta), vec3(*k))
+                    }
+                };
+                let roughness: Arc<dyn ScalarTexture> = match &description.roughness_texture {
+                    Some(name) => scalar(texture("roughness_texture", name)?),
+                    None => Arc::new(description.roughness.unwrap_or(0.0)),
+                };
+                let roughness_v: Arc<dyn ScalarTexture> =
+                    match (&description.roughness_v_texture, description.roughness_v) {
+                        (Some(name), _) => scalar(texture("roughness_v_texture", name)?),
+                        (None, Some(roughness_v)) => Arc::new(roughness_v),
+                        (None, None) => roughness.clone(),
+                    };
+                Arc::new(conductor.anisotropic_roughness_texture(roughness, roughness_v))
+            }
+        };
+        if let Some(name) = &description.normal_map {
+            let map = texture("normal_map", name)?;
+            return Ok(Arc::new(BumpMapped::normal_map(base, map)));
+        }
+        if let Some(name) = &description.bump {
+            let height = texture("bump", name)?;
+            let strength = description.bump_strength.unwrap_or(1.0);
+            return Ok(Arc::new(BumpMapped::height(base, height, strength)));
+        }
+        Ok(base)
+    }
+
+    /// Rejects the first field that is set but isn't among those the table's `type` uses.
+    fn only_fields<T>(
+        &self,
+        table: &Spanned<T>,
+        field: impl Fn(&str) -> String,
+        set: Vec<&str>,
+        used: &[&str],
+    ) -> Result<(), SceneError> {
+        match set.into_iter().find(|name| !used.contains(name)) {
+            Some(name) => Err(self.invalid(
+                self.field_offset(table, name),
+                field(name),
+                "not used by this type".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn required<'a, T, U>(
+        &self,
+        table: &Spanned<U>,
+        field: &str,
+        value: &'a Option<T>,
+    ) -> Result<&'a T, SceneError> {
+        value.as_ref().ok_or_else(|| {
+            self.invalid(
+                table.span().start,
+                field.to_string(),
+                "missing field".to_string(),
+            )
+        })
+    }
+
+    fn invalid(&self, offset: usize, field: String, message: String) -> SceneError {
+        let (line, column) = Self::position(&self.source, offset);
+        SceneError::Invalid {
+            path: self.path.clone(),
+            line,
+            column,
+            field: Some(field),
+            message,
+        }
+    }
+
+    /// The offset of the line that sets `key` within a table, or of the table itself if
+    /// the key can't be found.
+    fn field_offset<T>(&self, table: &Spanned<T>, key: &str) -> usize {
+        let span = table.span();
+        let mut offset = span.start;
+        for line in self.source[span].split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            if Self::key_of(trimmed) == Some(key) {
+                return offset + line.len() - trimmed.len();
+            }
+            offset += line.len();
+        }
+        table.span().start
+    }
+
+    fn key_of(line: &str) -> Option<&str> {
+        let (key, _) = line.split_once('=')?;
+        Some(key.trim()).filter(|key| !key.starts_with('['))
+    }
+
+    fn key_at(source: &str, offset: usize) -> Option<String> {
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = source[line_start..].lines().next().unwrap_or_default();
+        Self::key_of(line.trim_start()).map(str::to_string)
+    }
+
+    fn position(source: &str, offset: usize) -> (usize, usize) {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        (line, column)
+    }
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+/// The names of the fields that are set, out of those listed.
+fn set_fields<const N: usize>(fields: [(&'static str, bool); N]) -> Vec<&'static str> {
+    fields
+        .into_iter()
+        .filter_map(|(name, is_set)| is_set.then_some(name))
+        .collect()
+}
+
+// Range checks for numeric fields, made while parsing so that a bad value is reported at
+// its own line like any other type error.
+
+fn checked<'de, D, T>(
+    deserializer: D,
+    is_valid: impl Fn(f64) -> bool,
+    expected: &str,
+) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Copy + Into<f64>,
+{
+    let value = T::deserialize(deserializer)?;
+    if is_valid(value.into()) {
+        Ok(Some(value))
+    } else {
+        Err(de::Error::custom(format!("expected {}", expected)))
+    }
+}
+
+fn positive<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Copy + Into<f64>,
+{
+    checked(
+        deserializer,
+        |v| v.is_finite() && v > 0.0,
+        "a number above zero",
+    )
+}
+
+fn non_negative<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    checked(
+        deserializer,
+        |v| v.is_finite() && v >= 0.0,
+        "a number no less than zero",
+    )
+}
+
+fn finite<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    checked(deserializer, f64::is_finite, "a finite number")
+}
+
+fn unit_interval<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    checked(
+        deserializer,
+        |v| (0.0..=1.0).contains(&v),
+        "a number from 0 to 1",
+    )
+}
+
+fn field_of_view<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    checked(
+        deserializer,
+        |v| v > 0.0 && v < 180.0,
+        "an angle between 0 and 180 degrees",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{SceneError, SceneFile};
+
+    const SCENE: &str = r#"
+[camera]
+image_width = 64
+look_from = [13.0, 2.0, 3.0]
+
+[render]
+pixel_samples = 4
+
+[materials.ground]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[materials.glass]
+type = "dielectric"
+refractive_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0.0, -1000.0, 0.0]
+radius = 1000.0
+material = "ground"
+
+[[objects]]
+type = "triangle"
+vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+material = "glass"
+"#;
+
+    fn invalid(source: &str) -> (usize, Option<String>, String) {
+        match SceneFile::parse(source.to_string(), "test.toml").and_then(|s| s.build_scene()) {
+            Err(SceneError::Invalid {
+                line,
+                field,
+                message,
+                ..
+            }) => (line, field, message),
+            _ => panic!("expected the scene to be rejected"),
+        }
+    }
+
+    #[test]
+    fn scene_file_builds_camera_and_world() {
+        let scene = SceneFile::parse(SCENE.to_string(), "test.toml").unwrap();
+        let camera = scene.camera_builder().threads(1).build().unwrap();
+        let (world, warnings) = scene.build_scene().unwrap();
+        assert!(warnings.is_empty());
+        let film = camera.render(&world).unwrap();
+        assert_eq!(film.width(), 64);
+
+        let textured = SCENE.replace(
+            "albedo = [0.5, 0.5, 0.5]",
+            "texture = \"checks\"\n\n[textures.checks]\ntype = \"checker\"\n\
+             even = [0.2, 0.3, 0.1]\nodd = [0.9, 0.9, 0.9]\nscale = 0.5",
+        );
+        let scene = SceneFile::parse(textured, "test.toml").unwrap();
+        assert!(scene.build_scene().is_ok());
+
+        let rough = SCENE.replace(
+            "type = \"dielectric\"\nrefractive_index = 1.5",
+            "type = \"conductor\"\npreset = \"gold\"\nroughness_texture = \"smudges\"\n\n\
+             [materials.steel]\ntype = \"metal\"\nalbedo = [0.8, 0.8, 0.8]\n\
+             fuzz_texture = \"smudges\"\n\n[textures.smudges]\ntype = \"noise\"\n\
+             pattern = \"fbm\"",
+        );
+        assert_ne!(rough, SCENE);
+        let scene = SceneFile::parse(rough, "test.toml").unwrap();
+        assert!(scene.build_scene().is_ok());
+    }
+
+    #[test]
+    fn scene_file_reports_line_and_field() {
+        let (line, field, message) =
+            invalid(&SCENE.replace("material = \"glass\"", "material = \"steel\""));
+        assert_eq!(line, 26);
+        assert_eq!(field.as_deref(), Some("objects[1].material"));
+        assert_eq!(message, "undefined material 'steel'");
+
+        let (line, field, message) = invalid(&SCENE.replace("radius = 1000.0", "radius = \"big\""));
+        assert_eq!(line, 20);
+        assert_eq!(field.as_deref(), Some("radius"));
+        assert!(message.contains("invalid type"), "{message}");
+
+        let (line, field, message) = invalid(&SCENE.replace("radius = 1000.0\n", ""));
+        assert_eq!(line, 17);
+        assert_eq!(field.as_deref(), Some("objects[0].radius"));
+        assert_eq!(message, "missing field");
+
+        let (_, _, message) = invalid(&SCENE.replace("image_width", "width"));
+        assert!(message.contains("unknown field `width`"), "{message}");
+
+        let (line, field, _) = invalid(&format!("{SCENE}\n[environment]\ntype = \"solid\"\n"));
+        assert_eq!(line, 28);
+        assert_eq!(field.as_deref(), Some("environment.color"));
+
+        let (line, field, message) =
+            invalid(&SCENE.replace("albedo = [0.5, 0.5, 0.5]", "texture = \"checks\""));
+        assert_eq!(line, 11);
+        assert_eq!(field.as_deref(), Some("materials.ground.texture"));
+        assert_eq!(message, "undefined texture 'checks'");
+    }
+
+    #[test]
+    fn scene_file_rejects_bad_values_and_unused_fields() {
+        let (line, field, message) = invalid(&SCENE.replace("image_width = 64", "image_width = 0"));
+        assert_eq!(line, 3);
+        assert_eq!(field.as_deref(), Some("image_width"));
+        assert!(
+            message.contains("expected a number above zero"),
+            "{message}"
+        );
+
+        let (line, field, _) = invalid(&SCENE.replace("image_width = 64", "aspect_ratio = nan"));
+        assert_eq!(line, 3);
+        assert_eq!(field.as_deref(), Some("aspect_ratio"));
+
+        let (line, field, _) = invalid(&SCENE.replace("image_width = 64", "vert_fov = 0.0"));
+        assert_eq!(line, 3);
+        assert_eq!(field.as_deref(), Some("vert_fov"));
+
+        let (line, field, _) = invalid(&SCENE.replace("radius = 1000.0", "radius = -1.0"));
+        assert_eq!(line, 20);
+        assert_eq!(field.as_deref(), Some("radius"));
+
+        let (line, field, message) = invalid(&SCENE.replace(
+            "albedo = [0.5, 0.5, 0.5]",
+            "albedo = [0.5, 0.5, 0.5]\nfuzz = 0.2",
+        ));
+        assert_eq!(line, 12);
+        assert_eq!(field.as_deref(), Some("materials.ground.fuzz"));
+        assert_eq!(message, "not used by this type");
+
+        let (line, field, _) =
+            invalid(&SCENE.replace("material = \"glass\"", "material = \"glass\"\nradius = 1.0"));
+        assert_eq!(line, 27);
+        assert_eq!(field.as_deref(), Some("objects[1].radius"));
+    }
+}
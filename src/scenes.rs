@@ -0,0 +1,193 @@
+//! Scenes built in code rather than loaded from a file.
+
+use std::sync::Arc;
+
+use crate::bvh::Bvh;
+use crate::camera::CameraBuilder;
+use crate::environment::SolidColor;
+use crate::hittable::{Hittable, Sphere};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+/// Builds a scene and its camera from a seed for any random placement.
+pub type SceneBuilder = fn(u64) -> (CameraBuilder, Scene);
+
+/// The built-in scenes by name.
+pub const BUILTIN_SCENES: [(&str, SceneBuilder); 2] =
+    [("spheres", random_spheres), ("cornell", |_| cornell_box())];
+
+/// Builds the named scene, or returns `None` if there is no built-in scene by that name.
+pub fn builtin(name: &str, seed: u64) -> Option<(CameraBuilder, Scene)> {
+    BUILTIN_SCENES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, build)| build(seed))
+}
+
+/// The cover image of Ray Tracing in One Weekend: three large spheres surrounded by small
+/// ones with randomly chosen materials.
+pub fn random_spheres(seed: u64) -> (CameraBuilder, Scene) {
+    let cam = CameraBuilder::new(400, 16.0 / 9.0).look_from(Vec3::new(13.0, 2.0, 3.0));
+    let mut sampler = Sampler::new(seed);
+    // Every glass sphere shares this one material.
+    let glass: Arc<dyn Material> = Arc::new(Dielectric::new(1.5));
+
+    let mut world: Vec<Box<dyn Hittable>> = vec![
+        Box::new(Sphere {
+            // ground
+            center: Vec3::new(0.0, -1000.0, 0.0),
+            radius: 1000.0,
+            material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        }),
+        Box::new(Sphere {
+            center: Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            material: glass.clone(),
+        }),
+        Box::new(Sphere {
+            center: Vec3::new(-4.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Lambertian::new(Vec3::new(0.4, 0.2, 0.1)),
+        }),
+        Box::new(Sphere {
+            center: Vec3::new(4.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0),
+        }),
+    ];
+    for a in -11..11 {
+        for b in -11..11 {
+            let center = Vec3::new(
+                a as f32 + 0.9 * sampler.get_float(),
+                0.2,
+                b as f32 + 0.9 * sampler.get_float(),
+            );
+
+            if (&center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let choose_mat = sampler.get_float();
+                if choose_mat < 0.8 {
+                    let albedo = Vec3::random(&mut sampler) * Vec3::random(&mut sampler);
+                    world.push(Box::new(Sphere {
+                        center,
+                        radius: 0.2,
+                        material: Lambertian::new(albedo),
+                    }));
+                } else if choose_mat < 0.95 {
+                    let albedo = Vec3::random_in_range(&mut sampler, 0.5, 1.0);
+                    let fuzz = sampler.get_float();
+                    world.push(Box::new(Sphere {
+                        center,
+                        radius: 0.2,
+                        material: Metal::new(albedo, fuzz),
+                    }));
+                } else {
+                    world.push(Box::new(Sphere {
+                        center,
+                        radius: 0.2,
+                        material: glass.clone(),
+                    }));
+                }
+            }
+        }
+    }
+    (cam, Scene::new(Bvh::new(world)))
+}
+
+/// The Cornell box: a closed room with a red and a green wall, lit only by a square light
+/// in the ceiling, holding a tall box and a glass sphere.
+pub fn cornell_box() -> (CameraBuilder, Scene) {
+    let cam = CameraBuilder::new(400, 1.0)
+        .vert_fov(40.0)
+        .defocus_angle(0.0)
+        .look_from(Vec3::new(278.0, 278.0, -800.0))
+        .look_at(Vec3::new(278.0, 278.0, 0.0));
+
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let green: Arc<dyn Material> = Arc::new(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Vec3::new(15.0, 15.0, 15.0)));
+
+    let mut world: Vec<Box<dyn Hittable>> = Vec::new();
+    world.extend(quad(
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        green.clone(),
+    ));
+    world.extend(quad(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        red.clone(),
+    ));
+    // The light faces down, into the room.
+    world.extend(quad(
+        Vec3::new(343.0, 554.0, 332.0),
+        Vec3::new(-130.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -105.0),
+        light,
+    ));
+    world.extend(quad(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+        white.clone(),
+    ));
+    world.extend(quad(
+        Vec3::new(555.0, 555.0, 555.0),
+        Vec3::new(-555.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -555.0),
+        white.clone(),
+    ));
+    world.extend(quad(
+        Vec3::new(0.0, 0.0, 555.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        white.clone(),
+    ));
+
+    // A tall box, built from its six faces.
+    let (min, max) = (Vec3::new(265.0, 0.0, 295.0), Vec3::new(430.0, 330.0, 460.0));
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+    world.extend(quad(min.clone(), dx.clone(), dy.clone(), white.clone()));
+    world.extend(quad(min.clone(), dy.clone(), dz.clone(), white.clone()));
+    world.extend(quad(min.clone(), dz.clone(), dx.clone(), white.clone()));
+    world.extend(quad(max.clone(), &dx * -1.0, &dy * -1.0, white.clone()));
+    world.extend(quad(max.clone(), &dy * -1.0, &dz * -1.0, white.clone()));
+    world.extend(quad(max, &dz * -1.0, &dx * -1.0, white.clone()));
+
+    world.push(Box::new(Sphere {
+        center: Vec3::new(190.0, 90.0, 190.0),
+        radius: 90.0,
+        material: Dielectric::new(1.5),
+    }));
+
+    let scene =
+        Scene::new(Bvh::new(world)).with_environment(SolidColor::new(Vec3::new(0.0, 0.0, 0.0)));
+    (cam, scene)
+}
+
+/// The parallelogram with corner `q` and edges `u` and `v`, as two triangles facing along
+/// `u × v`.
+fn quad(q: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> [Box<dyn Hittable>; 2] {
+    let opposite = &(&q + &u) + &v;
+    [
+        Box::new(Triangle {
+            a: q.clone(),
+            b: &q + &u,
+            c: opposite.clone(),
+            material: material.clone(),
+        }),
+        Box::new(Triangle {
+            a: q.clone(),
+            b: opposite,
+            c: &q + &v,
+            material,
+        }),
+    ]
+}
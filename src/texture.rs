@@ -0,0 +1,419 @@
+//! Colours that vary over a surface, looked up at each hit.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+use image::{DynamicImage, ImageResult, Rgb32FImage};
+use serde::Deserialize;
+
+use crate::noise::Noise;
+use crate::numeric_utilities;
+use crate::vec3::Vec3;
+
+pub trait Texture: Send + Sync {
+    /// The colour at surface coordinates `uv` and world-space `point`.
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Vec3;
+}
+
+/// A shared handle to a texture, so that several materials can use one image.
+impl<T> Texture for Arc<T>
+where
+    T: Texture + ?Sized,
+{
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Vec3 {
+        (**self).value(uv, point)
+    }
+}
+
+/// A single number that varies over a surface, such as a material's roughness.
+pub trait ScalarTexture: Send + Sync {
+    /// The value at surface coordinates `uv` and world-space `point`.
+    fn scalar(&self, uv: (f32, f32), point: &Vec3) -> f32;
+}
+
+/// The same value everywhere.
+impl ScalarTexture for f32 {
+    fn scalar(&self, _uv: (f32, f32), _point: &Vec3) -> f32 {
+        *self
+    }
+}
+
+/// A shared handle to a scalar texture.
+impl ScalarTexture for Arc<dyn ScalarTexture> {
+    fn scalar(&self, uv: (f32, f32), point: &Vec3) -> f32 {
+        (**self).scalar(uv, point)
+    }
+}
+
+/// Any colour texture can stand in for a scalar one, giving the mean of its channels, so
+/// a greyscale image or black and white noise gives its level.
+impl<T> ScalarTexture for T
+where
+    T: Texture + ?Sized,
+{
+    fn scalar(&self, uv: (f32, f32), point: &Vec3) -> f32 {
+        let color = self.value(uv, point);
+        (color.x + color.y + color.z) / 3.0
+    }
+}
+
+/// The same colour everywhere.
+#[derive(Clone)]
+pub struct ConstantColor {
+    color: Vec3,
+}
+
+impl ConstantColor {
+    pub fn new(color: Vec3) -> Self {
+        ConstantColor { color }
+    }
+}
+
+impl Texture for ConstantColor {
+    fn value(&self, _uv: (f32, f32), _point: &Vec3) -> Vec3 {
+        self.color.clone()
+    }
+}
+
+/// A checkerboard filling space with cubes of side `scale`, so that it doesn't depend on
+/// how the surface is parameterised.
+pub struct SpatialChecker {
+    scale: f32,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl SpatialChecker {
+    pub fn new(scale: f32, even: impl Texture + 'static, odd: impl Texture + 'static) -> Self {
+        SpatialChecker {
+            scale,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        }
+    }
+}
+
+impl Texture for SpatialChecker {
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Vec3 {
+        let cell = |x: f32| (x / self.scale).floor() as i64;
+        if (cell(point.x) + cell(point.y) + cell(point.z)).rem_euclid(2) == 0 {
+            self.even.value(uv, point)
+        } else {
+            self.odd.value(uv, point)
+        }
+    }
+}
+
+/// A checkerboard in texture space, with `columns` squares across `u` and `rows` up `v`.
+pub struct UvChecker {
+    columns: f32,
+    rows: f32,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl UvChecker {
+    pub fn new(
+        columns: f32,
+        rows: f32,
+        even: impl Texture + 'static,
+        odd: impl Texture + 'static,
+    ) -> Self {
+        UvChecker {
+            columns,
+            rows,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        }
+    }
+}
+
+impl Texture for UvChecker {
+    fn value(&self, uv: (f32, f32), point: &Vec3) -> Vec3 {
+        let column = (uv.0 * self.columns).floor() as i64;
+        let row = (uv.1 * self.rows).floor() as i64;
+        if (column + row).rem_euclid(2) == 0 {
+            self.even.value(uv, point)
+        } else {
+            self.odd.value(uv, point)
+        }
+    }
+}
+
+/// How a [`NoiseTexture`] turns noise into a pattern.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoisePattern {
+    /// Smooth fractal noise.
+    #[default]
+    Fbm,
+    /// Fractal noise with sharp creases, like billowing smoke.
+    Turbulence,
+    /// Veins running across z, perturbed by turbulence.
+    Marble,
+    /// Growth rings around the y axis, perturbed by fractal noise.
+    Wood,
+    /// Worley cells, dark at the centre of each.
+    Worley,
+}
+
+/// A procedural texture evaluated at the hit point, blending from `low` to `high` as its
+/// pattern goes from zero to one.
+///
+/// `scale` is the frequency of the noise, so larger values give finer detail, and
+/// `octaves` is how many layers of ever finer noise are added together. The pattern is the
+/// same for the same seed.
+pub struct NoiseTexture {
+    noise: Noise,
+    pattern: NoisePattern,
+    scale: f32,
+    octaves: u32,
+    low: Vec3,
+    high: Vec3,
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, seed: u64) -> Self {
+        NoiseTexture {
+            noise: Noise::new(seed),
+            pattern,
+            scale: 1.0,
+            octaves: 7,
+            low: Vec3::new(0.0, 0.0, 0.0),
+            high: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    /// The colours at the bottom and top of the pattern, black and white by default.
+    pub fn colors(mut self, low: Vec3, high: Vec3) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// The pattern at `point`, in `[0, 1]`.
+    pub fn intensity(&self, point: &Vec3) -> f32 {
+        let p = point * self.scale;
+        let octaves = self.octaves;
+        let t = match self.pattern {
+            NoisePattern::Fbm => 0.5 * (1.0 + self.noise.fbm(&p, octaves)),
+            NoisePattern::Turbulence => self.noise.turbulence(&p, octaves),
+            NoisePattern::Marble => {
+                0.5 * (1.0 + (p.z + 10.0 * self.noise.turbulence(&p, octaves)).sin())
+            }
+            NoisePattern::Wood => {
+                let rings = p.x.hypot(p.z) + 0.5 * self.noise.fbm(&p, octaves);
+                (4.0 * rings).rem_euclid(1.0)
+            }
+            NoisePattern::Worley => self.noise.cellular(&p, octaves),
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _uv: (f32, f32), point: &Vec3) -> Vec3 {
+        let t = self.intensity(point);
+        &self.low * (1.0 - t) + &self.high * t
+    }
+}
+
+/// What an [`ImageTexture`] shows outside `[0, 1]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Tile the image, flipping every other copy so the seams match.
+    Mirror,
+    /// Stretch the edge texels outwards.
+    Clamp,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: i64) -> i64 {
+        match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            WrapMode::Clamp => i.clamp(0, size - 1),
+        }
+    }
+}
+
+/// An image mapped onto the surface by its UVs, with `(0, 0)` at the bottom left of the
+/// image, and filtered bilinearly.
+pub struct ImageTexture {
+    image: Rgb32FImage,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// Takes the texels as linear colours. An empty image is replaced by a single black
+    /// texel.
+    pub fn new(image: Rgb32FImage) -> Self {
+        let image = if image.width() == 0 || image.height() == 0 {
+            Rgb32FImage::new(1, 1)
+        } else {
+            image
+        };
+        ImageTexture {
+            image,
+            wrap: WrapMode::default(),
+        }
+    }
+
+    /// Loads any format the `image` crate can read. Integer images are taken to be sRGB
+    /// encoded and are converted to linear colour; floating-point ones are used as they
+    /// are. Empty images are rejected.
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        let image = Self::open(path.as_ref())?;
+        let is_linear = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let mut image = image.into_rgb32f();
+        if !is_linear {
+            for channel in image.iter_mut() {
+                *channel = numeric_utilities::srgb_to_linear(*channel);
+            }
+        }
+        Ok(ImageTexture::new(image))
+    }
+
+    /// Loads an image that holds data rather than colours, such as a normal map, taking
+    /// its values as they are.
+    pub fn load_data(path: impl AsRef<Path>) -> ImageResult<Self> {
+        Ok(ImageTexture::new(Self::open(path.as_ref())?.into_rgb32f()))
+    }
+
+    fn open(path: &Path) -> ImageResult<DynamicImage> {
+        let image = image::open(path)?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        Ok(image)
+    }
+
+    pub fn wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3 {
+        let x = self.wrap.apply(x, self.image.width() as i64);
+        let y = self.wrap.apply(y, self.image.height() as i64);
+        let pixel = self.image.get_pixel(x as u32, y as u32);
+        Vec3::new(pixel[0], pixel[1], pixel[2])
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, (u, v): (f32, f32), _point: &Vec3) -> Vec3 {
+        // Image rows run downwards, but v runs up.
+        let x = u * self.image.width() as f32 - 0.5;
+        let y = (1.0 - v) * self.image.height() as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let upper = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+        let lower = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+        upper * (1.0 - fy) + lower * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        ConstantColor, ImageTexture, NoisePattern, NoiseTexture, SpatialChecker, Texture,
+        UvChecker, WrapMode,
+    };
+    use crate::vec3::Vec3;
+    use image::{Rgb, Rgb32FImage};
+
+    #[test]
+    fn checkers_alternate() {
+        let black = ConstantColor::new(Vec3::new(0.0, 0.0, 0.0));
+        let white = ConstantColor::new(Vec3::new(1.0, 1.0, 1.0));
+        let spatial = SpatialChecker::new(0.5, white.clone(), black.clone());
+        let origin = Vec3::new(0.25, 0.25, 0.25);
+        assert_eq!(spatial.value((0.0, 0.0), &origin).x, 1.0);
+        assert_eq!(
+            spatial.value((0.0, 0.0), &Vec3::new(-0.25, 0.25, 0.25)).x,
+            0.0
+        );
+
+        let uv = UvChecker::new(4.0, 2.0, white, black);
+        assert_eq!(uv.value((0.1, 0.1), &origin).x, 1.0);
+        assert_eq!(uv.value((0.3, 0.1), &origin).x, 0.0);
+        assert_eq!(uv.value((0.3, 0.6), &origin).x, 1.0);
+    }
+
+    #[test]
+    fn noise_patterns_blend_between_colors() {
+        let low = Vec3::new(0.2, 0.1, 0.0);
+        let high = Vec3::new(0.9, 0.6, 0.3);
+        let uv = (0.0, 0.0);
+        for pattern in [
+            NoisePattern::Fbm,
+            NoisePattern::Turbulence,
+            NoisePattern::Marble,
+            NoisePattern::Wood,
+            NoisePattern::Worley,
+        ] {
+            let texture = NoiseTexture::new(pattern, 9)
+                .scale(3.0)
+                .octaves(4)
+                .colors(low.clone(), high.clone());
+            let same = NoiseTexture::new(pattern, 9).scale(3.0).octaves(4);
+            for i in 0..50 {
+                let point = Vec3::new(i as f32 * 0.37, 1.3, i as f32 * -0.11);
+                let t = texture.intensity(&point);
+                assert_eq!(t, same.intensity(&point));
+                let color = texture.value(uv, &point);
+                assert!((color.x - (0.2 + 0.7 * t)).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn image_texture_filters_and_wraps() {
+        // A black column on the left and a white one on the right.
+        let image = Rgb32FImage::from_fn(2, 1, |x, _| Rgb([x as f32; 3]));
+        let point = Vec3::new(0.0, 0.0, 0.0);
+        let repeat = ImageTexture::new(image.clone());
+        assert_eq!(repeat.value((0.25, 0.5), &point).x, 0.0);
+        assert_eq!(repeat.value((0.5, 0.5), &point).x, 0.5);
+        assert_eq!(repeat.value((1.25, 0.5), &point).x, 0.0);
+        // Halfway between the last texel and the first, which repeat blends and clamp
+        // doesn't.
+        assert_eq!(repeat.value((1.0, 0.5), &point).x, 0.5);
+        let clamp = ImageTexture::new(image).wrap(WrapMode::Clamp);
+        assert_eq!(clamp.value((1.0, 0.5), &point).x, 1.0);
+        assert_eq!(clamp.value((-3.0, 0.5), &point).x, 0.0);
+
+        let empty = ImageTexture::new(Rgb32FImage::new(0, 4)).wrap(WrapMode::Mirror);
+        assert_eq!(empty.value((0.5, 0.5), &point).y, 0.0);
+    }
+}
@@ -0,0 +1,216 @@
+//! Operators that compress the film's unbounded radiance into displayable `[0, 1]` values.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::vec3::Vec3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapOperator {
+    /// Clip anything brighter than white.
+    #[default]
+    Clamp,
+    /// `c / (1 + c)` on each channel.
+    Reinhard,
+    /// Reinhard on luminance, reaching white at [`ToneMapping::white_point`].
+    ExtendedReinhard,
+    /// Krzysztof Narkowicz's fit to the ACES filmic curve.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// Troy Sobotka's AgX, after Benjamin Wrensch's polynomial approximation.
+    Agx,
+}
+
+impl FromStr for ToneMapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.replace('-', "_").as_str() {
+            "clamp" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "extended_reinhard" => Ok(ToneMapOperator::ExtendedReinhard),
+            "aces" => Ok(ToneMapOperator::Aces),
+            "hable" => Ok(ToneMapOperator::Hable),
+            "agx" => Ok(ToneMapOperator::Agx),
+            _ => Err(format!(
+                "unknown tone mapper '{s}', expected one of clamp, reinhard, \
+                 extended-reinhard, aces, hable or agx"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ToneMapOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ToneMapOperator::Clamp => "clamp",
+            ToneMapOperator::Reinhard => "reinhard",
+            ToneMapOperator::ExtendedReinhard => "extended-reinhard",
+            ToneMapOperator::Aces => "aces",
+            ToneMapOperator::Hable => "hable",
+            ToneMapOperator::Agx => "agx",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How to turn linear radiance into display-referred linear values in `[0, 1]`, ready for
+/// the sRGB transfer function.
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops, applied before the operator.
+    pub exposure: f32,
+    /// The radiance that maps to white under [`ToneMapOperator::ExtendedReinhard`].
+    pub white_point: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::default(),
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        ToneMapping {
+            operator,
+            ..Default::default()
+        }
+    }
+
+    pub fn apply(&self, radiance: &Vec3) -> Vec3 {
+        let c = radiance * 2f32.powf(self.exposure);
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => c,
+            ToneMapOperator::Reinhard => per_channel(&c, |x| x / (1.0 + x)),
+            ToneMapOperator::ExtendedReinhard => self.extended_reinhard(&c),
+            ToneMapOperator::Aces => per_channel(&c, aces),
+            ToneMapOperator::Hable => {
+                let white_scale = 1.0 / hable_partial(HABLE_WHITE);
+                per_channel(&c, |x| hable_partial(x * HABLE_EXPOSURE_BIAS) * white_scale)
+            }
+            ToneMapOperator::Agx => agx(&c),
+        };
+        per_channel(&mapped, |x| x.clamp(0.0, 1.0))
+    }
+
+    fn extended_reinhard(&self, c: &Vec3) -> Vec3 {
+        let luminance = luminance(c);
+        if luminance <= 0.0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let white_squared = self.white_point * self.white_point;
+        let mapped = luminance * (1.0 + luminance / white_squared) / (1.0 + luminance);
+        c * (mapped / luminance)
+    }
+}
+
+fn per_channel(c: &Vec3, f: impl Fn(f32) -> f32) -> Vec3 {
+    Vec3::new(f(c.x), f(c.y), f(c.z))
+}
+
+fn luminance(c: &Vec3) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+fn aces(x: f32) -> f32 {
+    // The fit is for ACES output at its reference exposure, which is 0.6 of ours.
+    let x = x * 0.6;
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+const HABLE_WHITE: f32 = 11.2;
+const HABLE_EXPOSURE_BIAS: f32 = 2.0;
+
+fn hable_partial(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+const AGX_MIN_EV: f32 = -12.47393;
+const AGX_MAX_EV: f32 = 4.026069;
+
+// Rows of the matrices into and out of the AgX working space.
+const AGX_INSET: [[f32; 3]; 3] = [
+    [0.842_479_1, 0.078_433_6, 0.079_223_75],
+    [0.042_328_24, 0.878_468_6, 0.079_166_13],
+    [0.042_375_65, 0.078_433_6, 0.879_143],
+];
+const AGX_OUTSET: [[f32; 3]; 3] = [
+    [1.196_879, -0.098_020_88, -0.099_029_74],
+    [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+    [-0.052_971_64, -0.098_043_45, 1.151_073_7],
+];
+
+fn multiply(m: &[[f32; 3]; 3], c: &Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+        m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+        m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+    )
+}
+
+fn agx(c: &Vec3) -> Vec3 {
+    let inset = multiply(&AGX_INSET, c);
+    let encoded = per_channel(&inset, |x| {
+        let ev = x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV);
+        let x = (ev - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // The curve's output is display-encoded, so decode it back to linear for the sRGB
+    // transfer function that follows.
+    per_channel(&multiply(&AGX_OUTSET, &encoded), |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{ToneMapOperator, ToneMapping};
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn tone_mappers_stay_in_range_and_preserve_black() {
+        let operators = [
+            ToneMapOperator::Clamp,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::ExtendedReinhard,
+            ToneMapOperator::Aces,
+            ToneMapOperator::Hable,
+            ToneMapOperator::Agx,
+        ];
+        for operator in operators {
+            let mapping = ToneMapping::new(operator);
+            let black = mapping.apply(&Vec3::new(0.0, 0.0, 0.0));
+            assert!(black.length() < 1e-3, "{operator} lifts black");
+            let mut previous = 0.0;
+            for stop in -8..12 {
+                let grey = 2f32.powi(stop);
+                let mapped = mapping.apply(&Vec3::new(grey, grey, grey));
+                assert!((0.0..=1.0).contains(&mapped.y), "{operator} leaves [0, 1]");
+                assert!(mapped.y >= previous, "{operator} is not monotonic");
+                previous = mapped.y;
+            }
+            assert_eq!(
+                operator.to_string().parse::<ToneMapOperator>(),
+                Ok(operator)
+            );
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable, Impact, ShapeOnly};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+/// A single triangle. Its UVs are the barycentric weights of `b` and `c`, so `a` is at
+/// `(0, 0)`, `b` at `(1, 0)` and `c` at `(0, 1)`, and its tangents are the edges from `a`.
+pub struct Triangle<T> {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub material: T,
+}
+
+impl<T> Hittable for Triangle<T>
+where
+    T: Material,
+{
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        let (t, barycentric) = intersect(r, interval, [&self.a, &self.b, &self.c])?;
+        let impact = Impact::new(r, r.at(t), normal(&self.a, &self.b, &self.c), t)
+            .with_barycentric(barycentric);
+        Some(HitRecord::new(impact, &self.material, self))
+    }
+
+    fn surface_detail(&self, impact: Impact) -> Impact {
+        let Some(barycentric) = &impact.barycentric else {
+            return impact;
+        };
+        let uv = (barycentric.y, barycentric.z);
+        impact
+            .with_uv(uv)
+            .with_tangents(&self.b - &self.a, &self.c - &self.a)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_point_cloud([&self.a, &self.b, &self.c])
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        pdf_value([&self.a, &self.b, &self.c], origin, direction)
+    }
+
+    fn random_towards(&self, origin: &Vec3, sampler: &mut Sampler) -> Option<Vec3> {
+        Some(random_towards([&self.a, &self.b, &self.c], origin, sampler))
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        if self.material.is_emissive() {
+            lights.push(Box::new(Triangle {
+                a: self.a.clone(),
+                b: self.b.clone(),
+                c: self.c.clone(),
+                material: ShapeOnly,
+            }));
+        }
+    }
+}
+
+/// An indexed triangle mesh.
+///
+/// All faces share one vertex buffer and one material. The faces are kept in their own
+/// BVH, so a mesh can be dropped into a world as a single hittable.
+pub struct TriangleMesh {
+    faces: Bvh,
+}
+
+/// Per-vertex attributes of a mesh. Normals and UVs are optional, but when present they
+/// must have one entry per position.
+pub struct MeshVertices {
+    pub positions: Vec<Vec3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<(f32, f32)>>,
+}
+
+impl From<Vec<Vec3>> for MeshVertices {
+    fn from(positions: Vec<Vec3>) -> Self {
+        MeshVertices {
+            positions,
+            normals: None,
+            uvs: None,
+        }
+    }
+}
+
+struct MeshData<T> {
+    vertices: MeshVertices,
+    indices: Vec<[usize; 3]>,
+    material: T,
+}
+
+struct MeshFace<T> {
+    mesh: Arc<MeshData<T>>,
+    face: usize,
+}
+
+impl TriangleMesh {
+    /// Panics if any index is outside the vertex buffers, or if the normal or UV buffers
+    /// are a different length to the positions.
+    pub fn new<T>(vertices: impl Into<MeshVertices>, indices: Vec<[usize; 3]>, material: T) -> Self
+    where
+        T: Material + 'static,
+    {
+        let vertices = vertices.into();
+        let vertex_count = vertices.positions.len();
+        assert!(
+            vertices
+                .normals
+                .as_ref()
+                .is_none_or(|n| n.len() == vertex_count),
+            "triangle mesh needs one normal per vertex"
+        );
+        assert!(
+            vertices
+                .uvs
+                .as_ref()
+                .is_none_or(|uv| uv.len() == vertex_count),
+            "triangle mesh needs one UV per vertex"
+        );
+        assert!(
+            indices.iter().flatten().all(|&i| i < vertex_count),
+            "triangle mesh index out of range"
+        );
+        let face_count = indices.len();
+        let mesh = Arc::new(MeshData {
+            vertices,
+            indices,
+            material,
+        });
+        let faces = (0..face_count)
+            .map(|face| {
+                Box::new(MeshFace {
+                    mesh: mesh.clone(),
+                    face,
+                }) as Box<dyn Hittable>
+            })
+            .collect();
+        TriangleMesh {
+            faces: Bvh::new(faces),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        self.faces.intersect(r, interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.faces.bounding_box()
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        self.faces.collect_lights(lights)
+    }
+}
+
+impl<T> MeshFace<T> {
+    fn vertices(&self) -> [&Vec3; 3] {
+        let [a, b, c] = self.mesh.indices[self.face];
+        let positions = &self.mesh.vertices.positions;
+        [&positions[a], &positions[b], &positions[c]]
+    }
+
+    fn shading_normal(&self, barycentric: &Vec3) -> Option<Vec3> {
+        let normals = self.mesh.vertices.normals.as_ref()?;
+        let [a, b, c] = self.mesh.indices[self.face];
+        let normal =
+            &normals[a] * barycentric.x + &normals[b] * barycentric.y + &normals[c] * barycentric.z;
+        Some(normal.unit_vector())
+    }
+
+    /// The UVs of the face's corners. Without UVs in the mesh, the corners are mapped as
+    /// for a [`Triangle`].
+    fn uvs(&self) -> [(f32, f32); 3] {
+        let Some(uvs) = self.mesh.vertices.uvs.as_ref() else {
+            return [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+        };
+        let [a, b, c] = self.mesh.indices[self.face];
+        [uvs[a], uvs[b], uvs[c]]
+    }
+}
+
+impl<T> Hittable for MeshFace<T>
+where
+    T: Material + 'static,
+{
+    fn intersect(&self, r: &Ray, interval: &Interval<f32>) -> Option<HitRecord<'_>> {
+        let [a, b, c] = self.vertices();
+        let (t, barycentric) = intersect(r, interval, [a, b, c])?;
+        let impact = Impact::new(r, r.at(t), normal(a, b, c), t).with_barycentric(barycentric);
+        Some(HitRecord::new(impact, &self.mesh.material, self))
+    }
+
+    fn surface_detail(&self, impact: Impact) -> Impact {
+        let Some(barycentric) = impact.barycentric.clone() else {
+            return impact;
+        };
+        let uvs = self.uvs();
+        let (dpdu, dpdv) = tangents(self.vertices(), uvs);
+        let mut impact = impact.with_tangents(dpdu, dpdv);
+        if let Some(shading_normal) = self.shading_normal(&barycentric) {
+            impact = impact.with_shading_normal(shading_normal);
+        }
+        let weights = [barycentric.x, barycentric.y, barycentric.z];
+        let uv = uvs
+            .iter()
+            .zip(weights)
+            .fold((0.0, 0.0), |(u, v), (uv, weight)| {
+                (u + uv.0 * weight, v + uv.1 * weight)
+            });
+        impact.with_uv(uv)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_point_cloud(self.vertices())
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        pdf_value(self.vertices(), origin, direction)
+    }
+
+    fn random_towards(&self, origin: &Vec3, sampler: &mut Sampler) -> Option<Vec3> {
+        Some(random_towards(self.vertices(), origin, sampler))
+    }
+
+    /// Faces share their mesh, so only a handle to it is added for each.
+    fn collect_lights(&self, lights: &mut Vec<Box<dyn Hittable>>) {
+        if self.mesh.material.is_emissive() {
+            lights.push(Box::new(MeshFace {
+                mesh: self.mesh.clone(),
+                face: self.face,
+            }));
+        }
+    }
+}
+
+/// The density, per unit solid angle, with which [`random_towards`] picks `direction`.
+fn pdf_value([a, b, c]: [&Vec3; 3], origin: &Vec3, direction: &Vec3) -> f32 {
+    let r = Ray::new(origin.clone(), direction.clone());
+    let interval = Interval::new(0.001, f32::INFINITY);
+    let Some((t, _)) = intersect(&r, &interval, [a, b, c]) else {
+        return 0.0;
+    };
+    let cross = (b - a).cross(&(c - a));
+    let area = 0.5 * cross.length();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = cross.dot(direction).abs() / (cross.length() * direction.length());
+    distance_squared / (cosine * area)
+}
+
+/// The direction from `origin` to a point on the triangle picked uniformly by area.
+fn random_towards([a, b, c]: [&Vec3; 3], origin: &Vec3, sampler: &mut Sampler) -> Vec3 {
+    let su0 = sampler.get_float().sqrt();
+    let b0 = 1.0 - su0;
+    let b1 = sampler.get_float() * su0;
+    let point = a * b0 + b * b1 + c * (1.0 - b0 - b1);
+    &point - origin
+}
+
+fn normal(a: &Vec3, b: &Vec3, c: &Vec3) -> Vec3 {
+    (b - a).cross(&(c - a)).unit_vector()
+}
+
+/// The derivatives of the point on the triangle with the UVs given at its corners. If the
+/// UVs are degenerate, the edges from `a` are used instead.
+fn tangents([a, b, c]: [&Vec3; 3], [uv_a, uv_b, uv_c]: [(f32, f32); 3]) -> (Vec3, Vec3) {
+    let e1 = b - a;
+    let e2 = c - a;
+    let (du1, dv1) = (uv_b.0 - uv_a.0, uv_b.1 - uv_a.1);
+    let (du2, dv2) = (uv_c.0 - uv_a.0, uv_c.1 - uv_a.1);
+    let det = du1 * dv2 - dv1 * du2;
+    if det.abs() < 1e-12 {
+        return (e1, e2);
+    }
+    let dpdu = (&e1 * dv2 - &e2 * dv1) / det;
+    let dpdv = (&e2 * du1 - &e1 * du2) / det;
+    (dpdu, dpdv)
+}
+
+/// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013).
+///
+/// The vertices are transformed into a space where the ray runs down the z axis from the
+/// origin, so the edge tests become 2D and rays through a shared edge or vertex always hit
+/// exactly one of the adjoining triangles. Returns the ray parameter and the barycentric
+/// weights of `a`, `b` and `c`.
+fn intersect(r: &Ray, interval: &Interval<f32>, [a, b, c]: [&Vec3; 3]) -> Option<(f32, Vec3)> {
+    let d = &r.direction;
+    let kz = (0..3)
+        .max_by(|&i, &j| d[i].abs().total_cmp(&d[j].abs()))
+        .unwrap();
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+
+    let pa = a - &r.origin;
+    let pb = b - &r.origin;
+    let pc = c - &r.origin;
+    let ax = pa[kx] - sx * pa[kz];
+    let ay = pa[ky] - sy * pa[kz];
+    let bx = pb[kx] - sx * pb[kz];
+    let by = pb[ky] - sy * pb[kz];
+    let cx = pc[kx] - sx * pc[kz];
+    let cy = pc[ky] - sy * pc[kz];
+
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        // Fall back to double precision on edges so neighbouring triangles agree.
+        u = (cx as f64 * by as f64 - cy as f64 * bx as f64) as f32;
+        v = (ax as f64 * cy as f64 - ay as f64 * cx as f64) as f32;
+        w = (bx as f64 * ay as f64 - by as f64 * ax as f64) as f32;
+    }
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * sz * pa[kz] + v * sz * pb[kz] + w * sz * pc[kz]) / det;
+    if !interval.surrounds(&t) {
+        return None;
+    }
+    Some((t, Vec3::new(u / det, v / det, w / det)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Triangle, TriangleMesh};
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::ray::Ray;
+    use crate::sampler::Sampler;
+    use crate::scene::Scene;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn triangle_hit_reports_barycentrics() {
+        let triangle = Triangle {
+            a: Vec3::new(0.0, 0.0, 0.0),
+            b: Vec3::new(1.0, 0.0, 0.0),
+            c: Vec3::new(0.0, 1.0, 0.0),
+            material: Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        };
+        let r = Ray::new(Vec3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = triangle
+            .hit(&r, &Interval::new(0.001, f32::INFINITY))
+            .unwrap();
+        let barycentric = hr.impact.barycentric.unwrap();
+        assert_eq!(hr.impact.t, 1.0);
+        assert_eq!(barycentric.x, 0.25);
+        assert_eq!(barycentric.y, 0.25);
+        assert_eq!(barycentric.z, 0.5);
+        assert_eq!(hr.impact.uv, (0.25, 0.5));
+        assert_eq!(hr.impact.dpdu.x, 1.0);
+        assert_eq!(hr.impact.dpdv.y, 1.0);
+    }
+
+    #[test]
+    fn mesh_shared_edge_is_watertight() {
+        // A unit square split along its diagonal; rays through the diagonal must not leak.
+        let mesh = TriangleMesh::new(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(1.0, 1.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+            Lambertian::new(Vec3::new(0.5, 0.5, 0.5)),
+        );
+        let interval = Interval::new(0.001, f32::INFINITY);
+        for i in 1..100 {
+            let along = i as f32 / 100.0;
+            let r = Ray::new(Vec3::new(along, along, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            assert!(mesh.hit(&r, &interval).is_some());
+        }
+    }
+
+    #[test]
+    fn emissive_mesh_faces_become_lights() {
+        let square = |material| {
+            TriangleMesh::new(
+                vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::new(1.0, 1.0, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                ],
+                vec![[0, 1, 2], [0, 2, 3]],
+                material,
+            )
+        };
+        assert!(
+            Scene::new(square(DiffuseLight::new(Vec3::new(0.0, 0.0, 0.0))))
+                .lights
+                .is_empty()
+        );
+
+        let scene = Scene::new(square(DiffuseLight::new(Vec3::new(1.0, 1.0, 1.0))));
+        assert_eq!(scene.lights.len(), 2);
+        let origin = Vec3::new(0.5, 0.5, 1.0);
+        let direction = scene
+            .lights
+            .random_towards(&origin, &mut Sampler::new(3))
+            .unwrap();
+        assert!(direction.z < 0.0);
+        assert!(scene.lights.pdf_value(&origin, &direction) > 0.0);
+    }
+}
@@ -1,6 +1,5 @@
-use rand::distributions::{Standard, Uniform};
-use rand::prelude::*;
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+use crate::sampler::Sampler;
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Sub, SubAssign};
 
 #[derive(Clone, Default)]
 pub struct Vec3 {
@@ -14,53 +13,61 @@ impl Vec3 {
         Vec3 { x, y, z }
     }
 
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random(sampler: &mut Sampler) -> Self {
         Vec3 {
-            x: rng.sample(Standard),
-            y: rng.sample(Standard),
-            z: rng.sample(Standard),
+            x: sampler.get_float(),
+            y: sampler.get_float(),
+            z: sampler.get_float(),
         }
     }
 
-    pub fn random_in_range(min: f32, max: f32) -> Self {
-        let mut rng = rand::thread_rng();
-        let dist = Uniform::new_inclusive(min, max);
+    pub fn random_in_range(sampler: &mut Sampler, min: f32, max: f32) -> Self {
         Vec3 {
-            x: rng.sample(dist),
-            y: rng.sample(dist),
-            z: rng.sample(dist),
+            x: sampler.get_float_in_range(min, max),
+            y: sampler.get_float_in_range(min, max),
+            z: sampler.get_float_in_range(min, max),
         }
     }
 
-    pub fn random_in_unit_sphere() -> Self {
-        let mut rng = rand::thread_rng();
-        let dist = Uniform::new_inclusive(-1.0f32, 1.0f32);
+    pub fn random_in_unit_sphere(sampler: &mut Sampler) -> Self {
         loop {
-            let v = Vec3::new(rng.sample(dist), rng.sample(dist), rng.sample(dist));
+            let v = Vec3::random_in_range(sampler, -1.0, 1.0);
             if v.length_squared() < 1.0 {
                 return v;
             }
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
-        let dist = Uniform::new_inclusive(-1.0f32, 1.0f32);
+    pub fn random_in_unit_disk(sampler: &mut Sampler) -> Self {
         loop {
-            let v = Vec3::new(rng.sample(dist), rng.sample(dist), 0.0);
+            let v = Vec3::new(
+                sampler.get_float_in_range(-1.0, 1.0),
+                sampler.get_float_in_range(-1.0, 1.0),
+                0.0,
+            );
             if v.length_squared() < 1.0 {
                 return v;
             }
         }
     }
 
-    pub fn random_unit() -> Self {
-        Self::random_in_unit_sphere().unit_vector()
+    pub fn random_unit(sampler: &mut Sampler) -> Self {
+        Self::random_in_unit_sphere(sampler).unit_vector()
     }
 
-    pub fn random_unit_on_hemisphere(other: &Self) -> Self {
-        let random_unit = Self::random_unit();
+    /// A unit vector about the z axis, with a density proportional to its z component.
+    pub fn random_cosine_direction(sampler: &mut Sampler) -> Self {
+        let r1 = sampler.get_float();
+        let r2 = sampler.get_float();
+        let phi = 2.0 * std::f32::consts::PI * r1;
+        let x = phi.cos() * r2.sqrt();
+        let y = phi.sin() * r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+        Vec3::new(x, y, z)
+    }
+
+    pub fn random_unit_on_hemisphere(sampler: &mut Sampler, other: &Self) -> Self {
+        let random_unit = Self::random_unit(sampler);
         if other.dot(&random_unit) > 0.0 {
             random_unit
         } else {
@@ -104,11 +111,24 @@ impl Vec3 {
     pub fn refract(&self, normal: &Vec3, etai_over_etat: f32) -> Vec3 {
         let cos_theta = 1.0f32.min(normal.dot(&(self * -1.0)));
         let r_out_perp = (self + normal * cos_theta) * etai_over_etat;
-        let r_out_parallel = normal * (-1.0 * (1.0 - r_out_perp.length_squared()).abs().sqrt());
+        let r_out_parallel = normal * -(1.0 - r_out_perp.length_squared()).abs().sqrt();
         r_out_perp + r_out_parallel
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis {axis} is out of range"),
+        }
+    }
+}
+
 impl Add for &Vec3 {
     type Output = Vec3;
 